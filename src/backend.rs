@@ -0,0 +1,396 @@
+// File: src\backend.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Pluggable file operation backends
+// License: MIT
+
+//! File operation backends
+//!
+//! [`process_sources`](crate::process_sources) and
+//! [`perform_operation`](crate::perform_operation) decide *what* has to be
+//! copied or moved and hand the resulting list of [`Transfer`]s to a
+//! [`FileOpBackend`], which decides *how* it is done.
+
+use crate::error::XcomError;
+use crate::preserve::{self, Preserve};
+use crate::progress::ProgressTracker;
+use crate::report::ItemNotes;
+use crate::{native, FileOperation, OperationOptions};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// A single source path and the exact path it should end up at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub source: PathBuf,
+    pub target: PathBuf,
+}
+
+impl Transfer {
+    pub fn new(source: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+        Transfer {
+            source: source.into(),
+            target: target.into(),
+        }
+    }
+
+    /// Builds a transfer placing `source` inside the `dest` directory
+    pub fn into_dir(source: &Path, dest: &Path) -> Result<Self, XcomError> {
+        let name = match source.file_name() {
+            Some(name) => name.to_os_string(),
+            // Paths such as "." or ".." only have a name once resolved
+            None => source
+                .canonicalize()
+                .ok()
+                .and_then(|p| p.file_name().map(|n| n.to_os_string()))
+                .ok_or_else(|| XcomError::invalid(source, "invalid source path"))?,
+        };
+
+        Ok(Transfer::new(source, dest.join(name)))
+    }
+}
+
+/// State of the running operation, shared with the backend
+pub struct OperationContext<'a> {
+    pub options: &'a OperationOptions,
+    /// Engines that can should report every file and chunk written here
+    pub progress: &'a ProgressTracker,
+    /// Per-file details for the report, such as attributes that were lost
+    pub notes: &'a ItemNotes,
+}
+
+/// Carries out the transfers planned by the library
+pub trait FileOpBackend {
+    /// Short name of the backend, used in logs
+    fn name(&self) -> &'static str;
+
+    /// Copies or moves every transfer source to its target
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - Operation completed successfully
+    /// * `Ok(false)` - Operation was aborted by user
+    /// * `Err(XcomError::Aborted)` - Operation was aborted by user after
+    ///   some files had failed
+    /// * `Err(XcomError)` - Operation failed
+    fn execute(
+        &self,
+        transfers: &[Transfer],
+        operation: FileOperation,
+        context: &OperationContext,
+    ) -> Result<bool, XcomError>;
+
+    /// Creates a directory (and its missing parents) at the destination
+    fn create_dir(&self, path: &Path) -> Result<(), XcomError> {
+        fs::create_dir_all(path).map_err(|e| XcomError::io("create directory", path, e))
+    }
+
+    /// Removes a source directory emptied by a move
+    fn remove_dir(&self, path: &Path) -> Result<(), XcomError> {
+        fs::remove_dir(path).map_err(|e| XcomError::io("remove directory", path, e))
+    }
+
+    /// Makes `link` another name of the already copied file `original`,
+    /// replacing whatever is at `link`
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<(), XcomError> {
+        if fs::symlink_metadata(link).is_ok() {
+            fs::remove_file(link).map_err(|e| XcomError::io("replace", link, e))?;
+        }
+        fs::hard_link(original, link).map_err(|e| XcomError::io("create hard link", link, e))
+    }
+
+    /// Gives a directory created at the destination the attributes of its
+    /// source (read before the operation), once its contents are in place
+    ///
+    /// Returns the extended attributes the directory could not keep.
+    fn copy_dir_metadata(
+        &self,
+        dir: &Transfer,
+        metadata: &Metadata,
+        preserve: Preserve,
+    ) -> Result<Vec<String>, XcomError> {
+        preserve::apply(&dir.source, metadata, &dir.target, preserve)
+    }
+}
+
+/// Backend using `SHFileOperationW` from the Windows Shell
+#[cfg(windows)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShellBackend;
+
+#[cfg(windows)]
+impl FileOpBackend for ShellBackend {
+    fn name(&self) -> &'static str {
+        "shell"
+    }
+
+    fn execute(
+        &self,
+        transfers: &[Transfer],
+        operation: FileOperation,
+        context: &OperationContext,
+    ) -> Result<bool, XcomError> {
+        // The shell cannot be interrupted once started, only not started
+        if context.options.cancel.is_cancelled() {
+            return Ok(false);
+        }
+        crate::win32_shell_transfer(transfers, operation)
+    }
+}
+
+/// Backend using the pure Rust [`native`] engine
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NativeBackend;
+
+impl FileOpBackend for NativeBackend {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn execute(
+        &self,
+        transfers: &[Transfer],
+        operation: FileOperation,
+        context: &OperationContext,
+    ) -> Result<bool, XcomError> {
+        native::native_transfer(transfers, operation, context)
+    }
+}
+
+/// Backend that records the transfers it is given without touching the
+/// filesystem
+#[derive(Debug, Default)]
+pub struct DryRunBackend {
+    recorded: Mutex<Vec<(FileOperation, Transfer)>>,
+    created_dirs: Mutex<Vec<PathBuf>>,
+}
+
+impl DryRunBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every transfer recorded so far, in order
+    pub fn recorded(&self) -> Vec<(FileOperation, Transfer)> {
+        self.recorded.lock().unwrap().clone()
+    }
+
+    /// Returns every directory that would have been created, in order
+    pub fn created_dirs(&self) -> Vec<PathBuf> {
+        self.created_dirs.lock().unwrap().clone()
+    }
+}
+
+impl FileOpBackend for DryRunBackend {
+    fn name(&self) -> &'static str {
+        "dry-run"
+    }
+
+    fn execute(
+        &self,
+        transfers: &[Transfer],
+        operation: FileOperation,
+        _context: &OperationContext,
+    ) -> Result<bool, XcomError> {
+        let mut recorded = self.recorded.lock().unwrap();
+        recorded.extend(transfers.iter().map(|t| (operation, t.clone())));
+        Ok(true)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), XcomError> {
+        self.created_dirs.lock().unwrap().push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<(), XcomError> {
+        Ok(())
+    }
+
+    fn hard_link(&self, _original: &Path, _link: &Path) -> Result<(), XcomError> {
+        Ok(())
+    }
+
+    fn copy_dir_metadata(
+        &self,
+        _dir: &Transfer,
+        _metadata: &Metadata,
+        _preserve: Preserve,
+    ) -> Result<Vec<String>, XcomError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Backend for tests: records every call and returns a preset result
+#[derive(Debug)]
+pub struct MockBackend {
+    result: Result<bool, XcomError>,
+    calls: Mutex<Vec<(FileOperation, Vec<Transfer>)>>,
+    created_dirs: Mutex<Vec<PathBuf>>,
+}
+
+impl MockBackend {
+    /// Creates a mock whose every call returns `result`
+    pub fn new(result: Result<bool, XcomError>) -> Self {
+        MockBackend {
+            result,
+            calls: Mutex::new(Vec::new()),
+            created_dirs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the arguments of every `execute` call made so far
+    pub fn calls(&self) -> Vec<(FileOperation, Vec<Transfer>)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Returns every directory passed to `create_dir` so far
+    pub fn created_dirs(&self) -> Vec<PathBuf> {
+        self.created_dirs.lock().unwrap().clone()
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        MockBackend::new(Ok(true))
+    }
+}
+
+impl FileOpBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn execute(
+        &self,
+        transfers: &[Transfer],
+        operation: FileOperation,
+        _context: &OperationContext,
+    ) -> Result<bool, XcomError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((operation, transfers.to_vec()));
+        self.result.clone()
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), XcomError> {
+        self.created_dirs.lock().unwrap().push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<(), XcomError> {
+        Ok(())
+    }
+
+    fn hard_link(&self, _original: &Path, _link: &Path) -> Result<(), XcomError> {
+        Ok(())
+    }
+
+    fn copy_dir_metadata(
+        &self,
+        _dir: &Transfer,
+        _metadata: &Metadata,
+        _preserve: Preserve,
+    ) -> Result<Vec<String>, XcomError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Backend selection for the command-line tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    /// Windows Shell on Windows, native engine elsewhere
+    #[default]
+    Auto,
+    Shell,
+    Native,
+}
+
+impl BackendKind {
+    /// Instantiates the selected backend
+    pub fn create(self) -> Result<Box<dyn FileOpBackend>, XcomError> {
+        match self {
+            BackendKind::Auto => Ok(default_backend()),
+            #[cfg(windows)]
+            BackendKind::Shell => Ok(Box::new(ShellBackend)),
+            #[cfg(not(windows))]
+            BackendKind::Shell => Err(XcomError::Unsupported(
+                "The shell backend is only supported on Windows".to_string(),
+            )),
+            BackendKind::Native => Ok(Box::new(NativeBackend)),
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(BackendKind::Auto),
+            "shell" => Ok(BackendKind::Shell),
+            "native" => Ok(BackendKind::Native),
+            _ => Err(format!(
+                "Unknown backend '{}' (expected auto, shell or native)",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the preferred backend for the current platform
+pub fn default_backend() -> Box<dyn FileOpBackend> {
+    #[cfg(windows)]
+    {
+        Box::new(ShellBackend)
+    }
+    #[cfg(not(windows))]
+    {
+        Box::new(NativeBackend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_into_dir() {
+        let transfer = Transfer::into_dir(Path::new("a/b.txt"), Path::new("dest")).unwrap();
+        assert_eq!(transfer.source, PathBuf::from("a/b.txt"));
+        assert_eq!(transfer.target, PathBuf::from("dest/b.txt"));
+    }
+
+    #[test]
+    fn test_dry_run_records_transfers() {
+        let backend = DryRunBackend::new();
+        let transfers = vec![Transfer::new("a", "dest/a"), Transfer::new("b", "dest/b")];
+
+        let options = OperationOptions::default();
+        let progress = ProgressTracker::disabled();
+        let notes = ItemNotes::new();
+        let context = OperationContext {
+            options: &options,
+            progress: &progress,
+            notes: &notes,
+        };
+
+        assert_eq!(
+            backend.execute(&transfers, FileOperation::Move, &context),
+            Ok(true)
+        );
+
+        let recorded = backend.recorded();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[1].1, transfers[1]);
+    }
+
+    #[test]
+    fn test_backend_kind_from_str() {
+        assert_eq!("native".parse::<BackendKind>(), Ok(BackendKind::Native));
+        assert_eq!("AUTO".parse::<BackendKind>(), Ok(BackendKind::Auto));
+        assert!("ftp".parse::<BackendKind>().is_err());
+    }
+}
@@ -4,19 +4,27 @@
 // Description: A command-line tool to manage the Windows Recycle Bin using Rust
 // License: MIT
 
+#[cfg(windows)]
 use std::io::{self, Write};
+#[cfg(windows)]
 use std::env;
+#[cfg(windows)]
 use clap::{Arg, Command};
+#[cfg(windows)]
 use make_colors::*;
+#[cfg(windows)]
 use chrono::{DateTime, Local};
+#[cfg(windows)]
 use windows::{
     core::*,
     Win32::UI::Shell::*,
     Win32::System::Com::*,
 };
 
+#[cfg(windows)]
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+#[cfg(windows)]
 #[derive(Debug, Clone)]
 struct RecycleBinItem {
     name: String,
@@ -25,6 +33,7 @@ struct RecycleBinItem {
     size: u64,
 }
 
+#[cfg(windows)]
 fn print_logo() {
     println!("{}", make_colors(r#"
      _                                        _           _ _____ 
@@ -36,6 +45,7 @@ fn print_logo() {
 "#, "cyan", None));
 }
 
+#[cfg(windows)]
 fn initialize_com() -> Result<()> {
     unsafe {
         CoInitializeEx(None, COINIT_APARTMENTTHREADED)?;
@@ -43,12 +53,14 @@ fn initialize_com() -> Result<()> {
     Ok(())
 }
 
+#[cfg(windows)]
 fn uninitialize_com() {
     unsafe {
         CoUninitialize();
     }
 }
 
+#[cfg(windows)]
 fn list_recycle_bin() -> Result<Vec<RecycleBinItem>> {
     let mut items = Vec::new();
     
@@ -116,6 +128,7 @@ fn list_recycle_bin() -> Result<Vec<RecycleBinItem>> {
     Ok(items)
 }
 
+#[cfg(windows)]
 fn strret_to_string(strret: &STRRET, pidl: *mut ITEMIDLIST) -> String {
     unsafe {
         let mut buffer = [0u16; 260];
@@ -129,6 +142,7 @@ fn strret_to_string(strret: &STRRET, pidl: *mut ITEMIDLIST) -> String {
     }
 }
 
+#[cfg(windows)]
 fn display_recycle_bin_items(items: &[RecycleBinItem]) {
     if items.is_empty() {
         println!(
@@ -146,13 +160,14 @@ fn display_recycle_bin_items(items: &[RecycleBinItem]) {
         println!(
             "{}. [{}] {} - {}",
             make_colors_hex(&format!("{}", idx + 1), "#FF55FF", None).unwrap(),
-            make_colors("&date_str.to_string(), "yellow", None),
+            make_colors(&date_str.to_string(), "yellow", None),
             make_colors(&item.name, "cyan", None),
             make_colors_hex(&item.original_path, "#AAAAFF", None).unwrap()
         );
     }
 }
 
+#[cfg(windows)]
 fn empty_recycle_bin() -> Result<()> {
     unsafe {
         SHEmptyRecycleBinW(
@@ -166,6 +181,7 @@ fn empty_recycle_bin() -> Result<()> {
     Ok(())
 }
 
+#[cfg(windows)]
 fn restore_item(item: &RecycleBinItem) -> Result<()> {
     println!(
         "{} {}",
@@ -175,6 +191,7 @@ fn restore_item(item: &RecycleBinItem) -> Result<()> {
     Ok(())
 }
 
+#[cfg(windows)]
 fn delete_item_permanently(item: &RecycleBinItem) -> Result<()> {
     println!(
         "{} {}",
@@ -184,6 +201,7 @@ fn delete_item_permanently(item: &RecycleBinItem) -> Result<()> {
     Ok(())
 }
 
+#[cfg(windows)]
 fn parse_indices(input: &str, count: usize) -> Vec<usize> {
     let mut indices = Vec::new();
     
@@ -220,6 +238,7 @@ fn parse_indices(input: &str, count: usize) -> Vec<usize> {
     indices
 }
 
+#[cfg(windows)]
 fn interactive_mode() -> Result<()> {
     let mut items = list_recycle_bin()?;
     display_recycle_bin_items(&items);
@@ -342,6 +361,7 @@ fn interactive_mode() -> Result<()> {
     Ok(())
 }
 
+#[cfg(windows)]
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 2 && (args[1] == "-v" || args[1] == "--version") {
@@ -406,7 +426,13 @@ fn main() -> Result<()> {
     result
 }
 
-#[cfg(test)]
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("recyclebin is only supported on Windows");
+    std::process::exit(1);
+}
+
+#[cfg(all(test, windows))]
 mod tests {
     use super::*;
     
@@ -5,8 +5,7 @@
 // License: MIT
 
 use std::env;
-use std::path::Path;
-use xcom::{logs, process_sources, FileOperation};
+use xcom::{cli, logs, FileOperation};
 use clap_version_flag::colorful_version;
 
 fn main() {
@@ -16,15 +15,9 @@ fn main() {
         version.print_and_exit();
     }
 
-    if args.len() < 3 {
-        eprintln!("USAGE: {} SOURCE1 [SOURCE2 ...] DESTINATION", args[0]);
-        std::process::exit(1);
-    }
-
-    let sources = args[1..args.len() - 1].to_vec();
-    let dest = Path::new(&args[args.len() - 1]);
+    let args = cli::parse_args("xmove", "Move files and directories");
 
-    match process_sources(sources, dest, FileOperation::Move) {
+    match cli::run(args, FileOperation::Move) {
         Ok(_) => {
             // Operation completed successfully
         }
@@ -0,0 +1,481 @@
+// File: src\cli.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Command-line interface shared by copyx and xmove
+// License: MIT
+
+//! Command-line interface shared by `copyx` and `xmove`
+
+use crate::backend::BackendKind;
+use crate::cancel::CancellationToken;
+use crate::error::XcomError;
+use crate::filter::{self, EntryType, Filter};
+use crate::progress::TerminalProgress;
+use crate::{
+    process_sources, ConflictPolicy, FileOperation, FilterRules, FsyncMode, HashAlgorithm,
+    OperationOptions, Preserve, ReflinkMode, SparseMode, SymlinkMode,
+};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Command-line arguments of `copyx` and `xmove`
+#[derive(Parser, Debug)]
+#[command(disable_version_flag = true)]
+pub struct Args {
+    /// Source files, directories or wildcard patterns, followed by the destination directory
+    #[arg(value_name = "PATH", num_args = 2.., required = true)]
+    pub paths: Vec<String>,
+
+    /// Backend performing the operation: auto, shell or native. Options
+    /// only the native engine supports (--preserve, --sparse, --reflink,
+    /// --jobs, --verify, --resume, --fsync) make auto pick it, and are
+    /// rejected with shell
+    #[arg(long, value_name = "BACKEND", default_value = "auto")]
+    pub backend: BackendKind,
+
+    /// What to do when a destination file exists: overwrite, skip, rename,
+    /// newer-wins, larger-wins, skip-if-identical or prompt
+    #[arg(long, value_name = "POLICY", default_value = "overwrite")]
+    pub on_conflict: ConflictPolicy,
+
+    /// Print the planned actions without touching the filesystem
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Attributes kept on copies: mode, timestamps, ownership, xattr (with
+    /// ACLs), all or none (comma separated); without a value, all of them
+    #[arg(
+        long,
+        value_name = "ATTRS",
+        default_value = "mode,xattr",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    pub preserve: Preserve,
+
+    /// Symbolic links: copy them as links, follow them, or skip them
+    /// (moves never follow links)
+    #[arg(long, value_name = "MODE", default_value = "copy")]
+    pub symlinks: SymlinkMode,
+
+    /// Recreate hard links between copied files instead of copying their
+    /// data again (Unix only)
+    #[arg(short = 'H', long)]
+    pub hard_links: bool,
+
+    /// Holes in sparse files: keep them (auto), also turn runs of zeros
+    /// into holes (always), or write every byte (never)
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    pub sparse: SparseMode,
+
+    /// Copy-on-write clones (btrfs, XFS): use them when possible (auto),
+    /// fail files that cannot be cloned (always), or never
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    pub reflink: ReflinkMode,
+
+    /// Number of files copied or moved at once by the native engine
+    /// (0: one per CPU)
+    #[arg(short = 'j', long, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Read every copied file back and compare its checksum with the
+    /// source: blake3, sha256 or xxh3; without a value, blake3
+    #[arg(
+        long,
+        value_name = "ALGO",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "blake3"
+    )]
+    pub verify: Option<HashAlgorithm>,
+
+    /// Keep interrupted copies of large files and continue them on the
+    /// next run, after checking what was already copied
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Flush to the device the data of every file before it is renamed
+    /// into place (file), the directory after the rename (dir), both (all)
+    /// or nothing (none)
+    #[arg(long, value_name = "WHAT", default_value = "none")]
+    pub fsync: FsyncMode,
+
+    /// Leave out files and directories matching PATTERN, such as *.log,
+    /// target/ (directories only) or /build (at the top only)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Take along what matches PATTERN even if a later rule excludes it;
+    /// the first rule matching a path decides
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Read rules from FILE, one per line: + PATTERN or - PATTERN
+    #[arg(long, value_name = "FILE")]
+    pub filter_from: Vec<PathBuf>,
+
+    /// Leave out what git ignores in repositories among the sources
+    /// (.gitignore, .ignore and .git/info/exclude)
+    #[arg(long)]
+    pub respect_gitignore: bool,
+
+    /// Only files modified after AGE ago (30m, 12h, 2d, 1w) or after a
+    /// date (2026-01-01, 2026-01-01 12:00:00)
+    #[arg(long, value_name = "AGE", value_parser = filter::parse_time)]
+    pub newer_than: Option<SystemTime>,
+
+    /// Only files modified before AGE ago or before a date
+    #[arg(long, value_name = "AGE", value_parser = filter::parse_time)]
+    pub older_than: Option<SystemTime>,
+
+    /// Only files of at least SIZE bytes (K, M, G and T suffixes)
+    #[arg(long, value_name = "SIZE", value_parser = filter::parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Only files of at most SIZE bytes
+    #[arg(long, value_name = "SIZE", value_parser = filter::parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Only entries of TYPE: f (files), d (directories, without their
+    /// files) or l (links); repeat it to take several types
+    #[arg(long = "type", value_name = "TYPE")]
+    pub types: Vec<EntryType>,
+
+    /// The include, exclude and filter-from options, in command-line order
+    #[arg(skip)]
+    pub filters: Vec<FilterArg>,
+}
+
+/// An `--include`, `--exclude` or `--filter-from` option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterArg {
+    Include(String),
+    Exclude(String),
+    From(PathBuf),
+}
+
+impl Args {
+    /// Arguments from `matches`, with the filter options in the order
+    /// they were given
+    pub fn from_matches(matches: &ArgMatches) -> Result<Args, clap::Error> {
+        let mut args = Args::from_arg_matches(matches)?;
+
+        let includes = indexed(matches, "include").map(|(i, p)| (i, FilterArg::Include(p)));
+        let excludes = indexed(matches, "exclude").map(|(i, p)| (i, FilterArg::Exclude(p)));
+        let files = indexed(matches, "filter_from").map(|(i, p)| (i, FilterArg::From(p)));
+
+        let mut filters: Vec<_> = includes.chain(excludes).chain(files).collect();
+        filters.sort_by_key(|(index, _)| *index);
+
+        args.filters = filters.into_iter().map(|(_, filter)| filter).collect();
+        Ok(args)
+    }
+
+    /// Backend to create: the native engine when auto meets an option only
+    /// it supports
+    pub fn backend_kind(&self) -> Result<BackendKind, XcomError> {
+        match (self.backend, self.native_only_option()) {
+            (BackendKind::Shell, Some(option)) => Err(XcomError::Unsupported(format!(
+                "{} is not supported by the shell backend",
+                option
+            ))),
+            (BackendKind::Auto, Some(_)) => Ok(BackendKind::Native),
+            (kind, _) => Ok(kind),
+        }
+    }
+
+    /// The first option given that only the native engine carries out
+    fn native_only_option(&self) -> Option<&'static str> {
+        let given = [
+            ("--preserve", self.preserve != Preserve::default()),
+            ("--sparse", self.sparse != SparseMode::default()),
+            ("--reflink", self.reflink != ReflinkMode::default()),
+            ("--jobs", self.jobs != 1),
+            ("--verify", self.verify.is_some()),
+            ("--resume", self.resume),
+            ("--fsync", self.fsync != FsyncMode::default()),
+        ];
+        given
+            .into_iter()
+            .find(|(_, given)| *given)
+            .map(|(option, _)| option)
+    }
+
+    /// Metadata filter described by the age, size and type options
+    pub fn selection(&self) -> Option<Filter> {
+        let mut filters = Vec::new();
+        filters.extend(self.newer_than.map(Filter::NewerThan));
+        filters.extend(self.older_than.map(Filter::OlderThan));
+        filters.extend(self.min_size.map(Filter::MinSize));
+        filters.extend(self.max_size.map(Filter::MaxSize));
+
+        let types = self.types.iter().map(|kind| Filter::Type(*kind));
+        filters.extend(types.reduce(Filter::or));
+        filters.into_iter().reduce(Filter::and)
+    }
+
+    /// Filter rules described by the filter options
+    pub fn filter_rules(&self) -> Result<FilterRules, XcomError> {
+        let mut rules = FilterRules::new();
+        for filter in &self.filters {
+            match filter {
+                FilterArg::Include(pattern) => rules.include(pattern)?,
+                FilterArg::Exclude(pattern) => rules.exclude(pattern)?,
+                FilterArg::From(path) => rules.add_file(path)?,
+            }
+        }
+        Ok(rules)
+    }
+}
+
+/// Values of the option `id` along with their position on the command line
+fn indexed<T: Clone + Send + Sync + 'static>(
+    matches: &ArgMatches,
+    id: &str,
+) -> impl Iterator<Item = (usize, T)> {
+    let indices = matches.indices_of(id).into_iter().flatten();
+    let values = matches.get_many::<T>(id).into_iter().flatten().cloned();
+    indices.zip(values).collect::<Vec<_>>().into_iter()
+}
+
+/// Parses the command line of the binary called `name`
+pub fn parse_args(name: &'static str, about: &'static str) -> Args {
+    let matches = Args::command()
+        .name(name)
+        .bin_name(name)
+        .about(about)
+        .override_usage(format!(
+            "{} [OPTIONS] SOURCE1 [SOURCE2 ...] DESTINATION",
+            name
+        ))
+        .get_matches();
+
+    Args::from_matches(&matches).unwrap_or_else(|e| e.exit())
+}
+
+/// Runs `operation` as described by the parsed arguments
+///
+/// Prints a summary of the operation and fails if any item failed or the
+/// operation was interrupted by Ctrl-C or SIGTERM.
+pub fn run(args: Args, operation: FileOperation) -> Result<(), XcomError> {
+    let (dest, sources) = args
+        .paths
+        .split_last()
+        .ok_or_else(|| XcomError::invalid(Path::new(""), "missing destination"))?;
+
+    let backend = args.backend_kind()?.create()?;
+    let mut options = OperationOptions {
+        conflict: args.on_conflict,
+        dry_run: args.dry_run,
+        preserve: args.preserve,
+        symlinks: args.symlinks,
+        hard_links: args.hard_links,
+        sparse: args.sparse,
+        reflink: args.reflink,
+        jobs: args.jobs,
+        verify: args.verify,
+        resume: args.resume,
+        fsync: args.fsync,
+        filter: args.filter_rules()?,
+        respect_gitignore: args.respect_gitignore,
+        select: args.selection(),
+        ..Default::default()
+    };
+    if !args.dry_run && std::io::stderr().is_terminal() {
+        options.progress = Some(Arc::new(TerminalProgress::new()));
+    }
+    install_signal_handler(&options.cancel);
+
+    let report = process_sources(
+        sources.to_vec(),
+        Path::new(dest),
+        operation,
+        backend.as_ref(),
+        &options,
+    )?;
+
+    if report.dry_run {
+        for item in &report.items {
+            println!(
+                "{:<9} \"{}\" --> \"{}\"",
+                item.action(),
+                item.source.display(),
+                item.destination.display()
+            );
+        }
+    }
+
+    for item in &report.items {
+        if !item.lost_attributes.is_empty() {
+            eprintln!(
+                "Warning: \"{}\" lost attributes: {}",
+                item.destination.display(),
+                item.lost_attributes.join(", ")
+            );
+        }
+    }
+
+    for dir in &report.dirs {
+        if let Some(error) = &dir.error {
+            eprintln!("Warning: {}", error);
+        }
+        if !dir.lost_attributes.is_empty() {
+            eprintln!(
+                "Warning: \"{}\" lost attributes: {}",
+                dir.destination.display(),
+                dir.lost_attributes.join(", ")
+            );
+        }
+    }
+
+    for link in &report.dangling_links {
+        eprintln!("Warning: dangling link \"{}\"", link.display());
+    }
+    for link in &report.link_loops {
+        eprintln!(
+            "Warning: link \"{}\" points to a parent directory, not followed",
+            link.display()
+        );
+    }
+
+    println!("{}", report);
+
+    match report.error() {
+        Some(error) => Err(error),
+        None if report.aborted => Err(XcomError::Cancelled),
+        None => Ok(()),
+    }
+}
+
+/// Cancels `token` on Ctrl-C or SIGTERM, and exits on the second signal
+fn install_signal_handler(token: &CancellationToken) {
+    let token = token.clone();
+    let installed = ctrlc::set_handler(move || {
+        if token.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("\nCancelling, press Ctrl-C again to exit immediately");
+        token.cancel();
+    });
+
+    if let Err(e) = installed {
+        crate::logs(&format!("WARNING: cannot install signal handler: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_split_sources_and_destination() {
+        let args = Args::try_parse_from(["copyx", "a.txt", "b.txt", "dest"]).unwrap();
+        assert_eq!(args.paths, vec!["a.txt", "b.txt", "dest"]);
+        assert_eq!(args.backend, BackendKind::Auto);
+        assert_eq!(args.on_conflict, ConflictPolicy::Overwrite);
+    }
+
+    #[test]
+    fn test_args_on_conflict() {
+        let args = Args::try_parse_from(["xmove", "--on-conflict", "skip", "a", "dest"]).unwrap();
+        assert_eq!(args.on_conflict, ConflictPolicy::Skip);
+
+        let args = Args::try_parse_from(["copyx", "--symlinks", "follow", "a", "dest"]).unwrap();
+        assert_eq!(args.symlinks, SymlinkMode::Follow);
+    }
+
+    #[test]
+    fn test_args_preserve() {
+        let args = Args::try_parse_from(["copyx", "a", "dest"]).unwrap();
+        assert_eq!(args.preserve, Preserve::default());
+
+        let args = Args::try_parse_from(["copyx", "--preserve", "a", "dest"]).unwrap();
+        assert_eq!(args.preserve, Preserve::ALL);
+
+        let args = Args::try_parse_from(["copyx", "--preserve=timestamps", "a", "dest"]).unwrap();
+        assert!(args.preserve.timestamps && !args.preserve.mode);
+    }
+
+    #[test]
+    fn test_args_filters_keep_their_order() {
+        let matches = Args::command().get_matches_from([
+            "copyx",
+            "--exclude",
+            "*.log",
+            "--include",
+            "keep.log",
+            "--filter-from",
+            "rules",
+            "--exclude",
+            "target/",
+            "a",
+            "dest",
+        ]);
+        let args = Args::from_matches(&matches).unwrap();
+        assert_eq!(
+            args.filters,
+            vec![
+                FilterArg::Exclude("*.log".into()),
+                FilterArg::Include("keep.log".into()),
+                FilterArg::From("rules".into()),
+                FilterArg::Exclude("target/".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_selection() {
+        let args = Args::try_parse_from(["copyx", "a", "dest"]).unwrap();
+        assert_eq!(args.selection(), None);
+
+        let args = Args::try_parse_from([
+            "xmove",
+            "--min-size",
+            "10M",
+            "--type",
+            "f",
+            "--type",
+            "l",
+            "a",
+            "dest",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.selection(),
+            Some(Filter::All(vec![
+                Filter::MinSize(10 << 20),
+                Filter::Any(vec![
+                    Filter::Type(EntryType::File),
+                    Filter::Type(EntryType::Symlink)
+                ]),
+            ]))
+        );
+
+        assert!(Args::try_parse_from(["copyx", "--older-than", "2y", "a", "dest"]).is_err());
+    }
+
+    #[test]
+    fn test_args_native_only_options_pick_native_backend() {
+        let args = Args::try_parse_from(["copyx", "a", "dest"]).unwrap();
+        assert_eq!(args.backend_kind(), Ok(BackendKind::Auto));
+
+        let args = Args::try_parse_from(["copyx", "--verify", "a", "dest"]).unwrap();
+        assert_eq!(args.backend_kind(), Ok(BackendKind::Native));
+
+        let args =
+            Args::try_parse_from(["copyx", "--backend", "shell", "-j", "4", "a", "dest"]).unwrap();
+        assert!(matches!(
+            args.backend_kind(),
+            Err(XcomError::Unsupported(ref message)) if message.contains("--jobs")
+        ));
+    }
+
+    #[test]
+    fn test_args_require_destination() {
+        assert!(Args::try_parse_from(["copyx", "a.txt"]).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+// File: src\conflict.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Conflict resolution for existing destination files
+// License: MIT
+
+//! Conflict resolution
+//!
+//! Decides what happens when a destination file already exists, so that
+//! the outcome never depends on a dialog from the backend.
+
+use crate::error::XcomError;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What to do when the destination file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Keep the existing file and skip the source
+    Skip,
+    /// Keep both, giving the new file a " (n)" suffix
+    Rename,
+    /// Replace the existing file only if the source is newer
+    NewerWins,
+    /// Replace the existing file only if the source is larger
+    LargerWins,
+    /// Skip the source if both files have the same content
+    SkipIfIdentical,
+    /// Ask on the terminal for every conflict
+    Prompt,
+}
+
+impl ConflictPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::NewerWins => "newer-wins",
+            ConflictPolicy::LargerWins => "larger-wins",
+            ConflictPolicy::SkipIfIdentical => "skip-if-identical",
+            ConflictPolicy::Prompt => "prompt",
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "newer-wins" | "newer" => Ok(ConflictPolicy::NewerWins),
+            "larger-wins" | "larger" => Ok(ConflictPolicy::LargerWins),
+            "skip-if-identical" | "identical" => Ok(ConflictPolicy::SkipIfIdentical),
+            "prompt" => Ok(ConflictPolicy::Prompt),
+            _ => Err(format!(
+                "Unknown conflict policy '{}' (expected overwrite, skip, rename, newer-wins, \
+                 larger-wins, skip-if-identical or prompt)",
+                s
+            )),
+        }
+    }
+}
+
+/// Outcome of resolving a single transfer against the destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Nothing exists at the target yet
+    Create,
+    /// The existing target is replaced
+    Overwrite,
+    /// The source goes to this free path instead
+    Rename(PathBuf),
+    /// The source is left alone
+    Skip,
+}
+
+/// Resolves conflicts for a batch of transfers
+///
+/// Targets claimed earlier in the same batch count as existing, so two
+/// sources with the same name never silently land on the same path.
+#[derive(Debug)]
+pub struct ConflictResolver {
+    policy: ConflictPolicy,
+    // Planned target -> source that will be written there
+    planned: HashMap<PathBuf, PathBuf>,
+}
+
+impl ConflictResolver {
+    pub fn new(policy: ConflictPolicy) -> Self {
+        ConflictResolver {
+            policy,
+            planned: HashMap::new(),
+        }
+    }
+
+    /// Decides what to do with `source` given its intended `target`
+    pub fn resolve(&mut self, source: &Path, target: &Path) -> Result<Resolution, XcomError> {
+        // The file currently "at" the target: on disk, or planned by this batch
+        let existing = match self.planned.get(target) {
+            Some(planned_source) => planned_source.clone(),
+            None if target.symlink_metadata().is_ok() => target.to_path_buf(),
+            None => {
+                self.claim(source, target);
+                return Ok(Resolution::Create);
+            }
+        };
+
+        let resolution = match self.policy {
+            ConflictPolicy::Overwrite => Resolution::Overwrite,
+            ConflictPolicy::Skip => Resolution::Skip,
+            ConflictPolicy::Rename => Resolution::Rename(self.free_name(target)),
+            ConflictPolicy::NewerWins => {
+                if modified(source)? > modified(&existing)? {
+                    Resolution::Overwrite
+                } else {
+                    Resolution::Skip
+                }
+            }
+            ConflictPolicy::LargerWins => {
+                if size(source)? > size(&existing)? {
+                    Resolution::Overwrite
+                } else {
+                    Resolution::Skip
+                }
+            }
+            ConflictPolicy::SkipIfIdentical => {
+                if same_content(source, &existing)? {
+                    Resolution::Skip
+                } else {
+                    Resolution::Overwrite
+                }
+            }
+            ConflictPolicy::Prompt => self.prompt(target)?,
+        };
+
+        match &resolution {
+            Resolution::Overwrite => self.claim(source, target),
+            Resolution::Rename(renamed) => self.claim(source, renamed),
+            _ => {}
+        }
+
+        Ok(resolution)
+    }
+
+    fn claim(&mut self, source: &Path, target: &Path) {
+        self.planned
+            .insert(target.to_path_buf(), source.to_path_buf());
+    }
+
+    /// Finds the first "name (n).ext" that is neither on disk nor planned
+    fn free_name(&self, target: &Path) -> PathBuf {
+        let stem = target
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension = target
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+
+        let mut n = 1;
+        loop {
+            let candidate = target.with_file_name(format!("{} ({}){}", stem, n, extension));
+            if candidate.symlink_metadata().is_err() && !self.planned.contains_key(&candidate) {
+                return candidate;
+            }
+            n += 1;
+        }
+    }
+
+    fn prompt(&mut self, target: &Path) -> Result<Resolution, XcomError> {
+        let stdin = io::stdin();
+
+        loop {
+            eprint!(
+                "\"{}\" already exists. [o]verwrite, [s]kip, [r]ename, overwrite [a]ll, skip a[l]l: ",
+                target.display()
+            );
+            let _ = io::stderr().flush();
+
+            let mut input = String::new();
+            let read = stdin
+                .lock()
+                .read_line(&mut input)
+                .map_err(|e| XcomError::io("read answer for", target, e))?;
+            if read == 0 {
+                // No terminal to answer from: leave the existing file alone
+                return Ok(Resolution::Skip);
+            }
+
+            match input.trim().to_lowercase().as_str() {
+                "o" => return Ok(Resolution::Overwrite),
+                "s" => return Ok(Resolution::Skip),
+                "r" => return Ok(Resolution::Rename(self.free_name(target))),
+                "a" => {
+                    self.policy = ConflictPolicy::Overwrite;
+                    return Ok(Resolution::Overwrite);
+                }
+                "l" => {
+                    self.policy = ConflictPolicy::Skip;
+                    return Ok(Resolution::Skip);
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Result<std::time::SystemTime, XcomError> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| XcomError::io("read", path, e))
+}
+
+fn size(path: &Path) -> Result<u64, XcomError> {
+    fs::metadata(path)
+        .map(|m| m.len())
+        .map_err(|e| XcomError::io("read", path, e))
+}
+
+/// Compares two files byte by byte (after a cheap size check)
+fn same_content(a: &Path, b: &Path) -> Result<bool, XcomError> {
+    if size(a)? != size(b)? {
+        return Ok(false);
+    }
+
+    let open = |path: &Path| File::open(path).map_err(|e| XcomError::io("open", path, e));
+    let mut file_a = open(a)?;
+    let mut file_b = open(b)?;
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+
+    loop {
+        let read_a = read_full(&mut file_a, &mut buf_a)
+            .map_err(|e| XcomError::io("read", a, e))?;
+        let read_b = read_full(&mut file_b, &mut buf_b)
+            .map_err(|e| XcomError::io("read", b, e))?;
+
+        if read_a != read_b || buf_a[..read_a] != buf_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_policy_from_str() {
+        assert_eq!("skip".parse::<ConflictPolicy>(), Ok(ConflictPolicy::Skip));
+        assert_eq!("newer-wins".parse::<ConflictPolicy>(), Ok(ConflictPolicy::NewerWins));
+        assert!("ask-mom".parse::<ConflictPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_rename_picks_free_suffix() {
+        let tmp = tempdir().unwrap();
+        let source = tmp.path().join("source.txt");
+        let target = tmp.path().join("report.txt");
+        fs::write(&source, "new").unwrap();
+        fs::write(&target, "old").unwrap();
+        fs::write(tmp.path().join("report (1).txt"), "older").unwrap();
+
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Rename);
+
+        assert_eq!(
+            resolver.resolve(&source, &target),
+            Ok(Resolution::Rename(tmp.path().join("report (2).txt")))
+        );
+        // The suffix claimed above is not handed out twice
+        assert_eq!(
+            resolver.resolve(&source, &target),
+            Ok(Resolution::Rename(tmp.path().join("report (3).txt")))
+        );
+    }
+
+    #[test]
+    fn test_larger_wins_and_identical() {
+        let tmp = tempdir().unwrap();
+        let small = tmp.path().join("small");
+        let large = tmp.path().join("large");
+        let copy = tmp.path().join("copy");
+        fs::write(&small, "1").unwrap();
+        fs::write(&large, "12345").unwrap();
+        fs::write(&copy, "12345").unwrap();
+
+        let mut larger = ConflictResolver::new(ConflictPolicy::LargerWins);
+        assert_eq!(larger.resolve(&small, &large), Ok(Resolution::Skip));
+        assert_eq!(larger.resolve(&large, &small), Ok(Resolution::Overwrite));
+
+        let mut identical = ConflictResolver::new(ConflictPolicy::SkipIfIdentical);
+        assert_eq!(identical.resolve(&large, &copy), Ok(Resolution::Skip));
+        assert_eq!(identical.resolve(&small, &copy), Ok(Resolution::Overwrite));
+    }
+
+    #[test]
+    fn test_duplicate_targets_in_batch_conflict() {
+        let tmp = tempdir().unwrap();
+        let target = tmp.path().join("dest/a.txt");
+
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Skip);
+        assert_eq!(resolver.resolve(Path::new("x/a.txt"), &target), Ok(Resolution::Create));
+        assert_eq!(resolver.resolve(Path::new("y/a.txt"), &target), Ok(Resolution::Skip));
+    }
+}
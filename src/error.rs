@@ -0,0 +1,200 @@
+// File: src\error.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Error type of the xcom library
+// License: MIT
+
+//! Error handling
+//!
+//! [`XcomError`] keeps enough structure for callers to tell a missing file
+//! from a permission problem or a full disk, and to retry or report the
+//! individual files of an operation that failed.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors reported by xcom operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XcomError {
+    /// A filesystem call failed on `path`
+    Io {
+        /// What was being done, e.g. "copy" or "read directory"
+        operation: &'static str,
+        path: PathBuf,
+        kind: io::ErrorKind,
+        /// Raw OS error code (errno or Win32 error), when there is one
+        os_code: Option<i32>,
+        message: String,
+    },
+    /// `SHFileOperationW` returned a non-zero code
+    Shell { code: i32 },
+    /// The request cannot be carried out as given
+    Invalid { path: PathBuf, reason: String },
+    /// The feature is not available on this platform
+    Unsupported(String),
+    /// Some files of the operation failed while the others went through
+    Failures(Vec<FileFailure>),
+    /// The operation was cancelled after some of its files had failed
+    Aborted(Vec<FileFailure>),
+    /// The operation was cancelled through its
+    /// [`CancellationToken`](crate::CancellationToken)
+    Cancelled,
+}
+
+/// A single file that could not be copied or moved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFailure {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub error: XcomError,
+}
+
+impl XcomError {
+    /// Wraps an I/O error raised while performing `operation` on `path`
+    pub fn io(operation: &'static str, path: &Path, error: io::Error) -> Self {
+        XcomError::Io {
+            operation,
+            path: path.to_path_buf(),
+            kind: error.kind(),
+            os_code: error.raw_os_error(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn invalid(path: &Path, reason: impl Into<String>) -> Self {
+        XcomError::Invalid {
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        }
+    }
+
+    /// The `io::ErrorKind` behind this error, if it comes from the filesystem
+    ///
+    /// For [`XcomError::Failures`] and [`XcomError::Aborted`] this is the
+    /// kind of the first failure.
+    pub fn kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            XcomError::Io { kind, .. } => Some(*kind),
+            XcomError::Failures(failures) | XcomError::Aborted(failures) => {
+                failures.first().and_then(|f| f.error.kind())
+            }
+            _ => None,
+        }
+    }
+
+    /// The raw OS error code, if any
+    pub fn os_code(&self) -> Option<i32> {
+        match self {
+            XcomError::Io { os_code, .. } => *os_code,
+            XcomError::Shell { code } => Some(*code),
+            XcomError::Failures(failures) | XcomError::Aborted(failures) => {
+                failures.first().and_then(|f| f.error.os_code())
+            }
+            _ => None,
+        }
+    }
+
+    /// The path the error relates to, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            XcomError::Io { path, .. } | XcomError::Invalid { path, .. } => Some(path),
+            XcomError::Failures(failures) | XcomError::Aborted(failures) => {
+                failures.first().map(|f| f.source.as_path())
+            }
+            _ => None,
+        }
+    }
+
+    /// The per-file failures carried by this error
+    pub fn failures(&self) -> &[FileFailure] {
+        match self {
+            XcomError::Failures(failures) | XcomError::Aborted(failures) => failures,
+            _ => &[],
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == Some(io::ErrorKind::NotFound)
+    }
+
+    pub fn is_permission_denied(&self) -> bool {
+        self.kind() == Some(io::ErrorKind::PermissionDenied)
+    }
+
+    pub fn is_out_of_space(&self) -> bool {
+        self.kind() == Some(io::ErrorKind::StorageFull)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, XcomError::Cancelled | XcomError::Aborted(_))
+    }
+}
+
+impl fmt::Display for XcomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XcomError::Io {
+                operation,
+                path,
+                message,
+                ..
+            } => write!(f, "Failed to {} {}: {}", operation, path.display(), message),
+            XcomError::Shell { code } => write!(f, "SHFileOperation failed: 0x{:08x}", code),
+            XcomError::Invalid { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            XcomError::Unsupported(what) => f.write_str(what),
+            XcomError::Failures(failures) => {
+                write!(f, "{} file(s) failed", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  {}", failure.error)?;
+                }
+                Ok(())
+            }
+            XcomError::Aborted(failures) => {
+                write!(f, "Operation cancelled, {} file(s) failed", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  {}", failure.error)?;
+                }
+                Ok(())
+            }
+            XcomError::Cancelled => f.write_str("Operation cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for XcomError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_keeps_kind_and_path() {
+        let error = XcomError::io(
+            "copy",
+            Path::new("a.txt"),
+            io::Error::from(io::ErrorKind::NotFound),
+        );
+
+        assert!(error.is_not_found());
+        assert_eq!(error.path(), Some(Path::new("a.txt")));
+        assert!(error.to_string().starts_with("Failed to copy a.txt"));
+    }
+
+    #[test]
+    fn test_failures_expose_first_error() {
+        let error = XcomError::Failures(vec![FileFailure {
+            source: PathBuf::from("a"),
+            target: PathBuf::from("dest/a"),
+            error: XcomError::io(
+                "copy",
+                Path::new("a"),
+                io::Error::from_raw_os_error(13),
+            ),
+        }]);
+
+        assert_eq!(error.failures().len(), 1);
+        assert_eq!(error.os_code(), Some(13));
+        assert_eq!(error.path(), Some(Path::new("a")));
+    }
+}
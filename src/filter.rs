@@ -0,0 +1,423 @@
+// File: src\filter.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Rules and predicates selecting the files to transfer
+// License: MIT
+
+//! Filter rules
+//!
+//! [`FilterRules`] decide, like rsync's `--include` and `--exclude`, which
+//! files and directories an operation takes along. Rules are tried in the
+//! order they were added and the first one matching a path decides; paths
+//! no rule matches are included. An excluded directory is left out with
+//! everything below it.
+//!
+//! Patterns are matched against paths relative to the destination, e.g.
+//! `src/main.rs` for a file copied to `dest/src/main.rs`:
+//!
+//! - a pattern without `/`, such as `*.log`, matches names at any depth
+//! - a trailing `/`, as in `target/`, only matches directories
+//! - a leading `/`, as in `/build`, only matches at the top
+//! - `*` stays within a name, `**` spans directories
+//!
+//! A [`Filter`] then selects files by their metadata: age, size and type,
+//! combined with [`Filter::and`], [`Filter::or`] and `!`. Directories are
+//! always walked and recreated; filters select the entries inside them.
+
+use crate::error::XcomError;
+use crate::pattern;
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use globset::GlobMatcher;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::ops::Not;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// Whether a rule takes matching paths along or leaves them out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Include,
+    Exclude,
+}
+
+/// One include or exclude rule
+#[derive(Debug, Clone)]
+struct Rule {
+    kind: RuleKind,
+    matcher: GlobMatcher,
+    dirs_only: bool,
+}
+
+/// Ordered include and exclude rules; empty rules include everything
+#[derive(Debug, Clone, Default)]
+pub struct FilterRules {
+    rules: Vec<Rule>,
+}
+
+impl FilterRules {
+    pub fn new() -> Self {
+        FilterRules::default()
+    }
+
+    /// Adds a rule after the existing ones
+    pub fn add(&mut self, kind: RuleKind, pattern: &str) -> Result<(), XcomError> {
+        let dirs_only = pattern.ends_with('/');
+        let trimmed = pattern.trim_end_matches('/');
+        let glob = match trimmed.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None => format!("**/{}", trimmed),
+        };
+
+        let matcher = pattern::build_glob(&glob)
+            .map_err(|e| XcomError::invalid(Path::new(pattern), e.to_string()))?
+            .compile_matcher();
+        self.rules.push(Rule {
+            kind,
+            matcher,
+            dirs_only,
+        });
+        Ok(())
+    }
+
+    pub fn include(&mut self, pattern: &str) -> Result<(), XcomError> {
+        self.add(RuleKind::Include, pattern)
+    }
+
+    pub fn exclude(&mut self, pattern: &str) -> Result<(), XcomError> {
+        self.add(RuleKind::Exclude, pattern)
+    }
+
+    /// Adds the rules listed in the file at `path`
+    ///
+    /// Each line holds `+ PATTERN` or `include PATTERN` to include,
+    /// `- PATTERN` or `exclude PATTERN` to exclude. Empty lines and lines
+    /// starting with `#` or `;` are ignored.
+    pub fn add_file(&mut self, path: &Path) -> Result<(), XcomError> {
+        let contents = fs::read_to_string(path).map_err(|e| XcomError::io("read", path, e))?;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim_start().is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            let (kind, pattern) = match line.split_once(' ') {
+                Some(("+" | "include", pattern)) => (RuleKind::Include, pattern),
+                Some(("-" | "exclude", pattern)) => (RuleKind::Exclude, pattern),
+                _ => {
+                    return Err(XcomError::invalid(
+                        path,
+                        format!("line {}: expected '+ PATTERN' or '- PATTERN'", number + 1),
+                    ))
+                }
+            };
+            self.add(kind, pattern)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the path `relative` to the destination is left out
+    pub fn excludes(&self, relative: &Path, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .find(|rule| (is_dir || !rule.dirs_only) && rule.matcher.is_match(relative))
+            .is_some_and(|rule| rule.kind == RuleKind::Exclude)
+    }
+}
+
+/// Kind of filesystem entry a [`Filter`] can select
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl EntryType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EntryType::File => "f",
+            EntryType::Dir => "d",
+            EntryType::Symlink => "l",
+        }
+    }
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EntryType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "f" | "file" => Ok(EntryType::File),
+            "d" | "dir" | "directory" => Ok(EntryType::Dir),
+            "l" | "link" | "symlink" => Ok(EntryType::Symlink),
+            _ => Err(format!("Unknown entry type '{}' (expected f, d or l)", s)),
+        }
+    }
+}
+
+/// Selects files by their metadata, e.g. files not modified for a week or
+/// larger than 1 GiB:
+/// `Filter::OlderThan(week_ago).or(Filter::MinSize(1 << 30))`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Modified after the given time
+    NewerThan(SystemTime),
+    /// Modified before the given time
+    OlderThan(SystemTime),
+    /// At least this many bytes
+    MinSize(u64),
+    /// At most this many bytes
+    MaxSize(u64),
+    Type(EntryType),
+    /// Every filter matches
+    All(Vec<Filter>),
+    /// At least one filter matches
+    Any(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Matches what both `self` and `other` match
+    pub fn and(self, other: Filter) -> Filter {
+        match self {
+            Filter::All(mut filters) => {
+                filters.push(other);
+                Filter::All(filters)
+            }
+            filter => Filter::All(vec![filter, other]),
+        }
+    }
+
+    /// Matches what `self` or `other` matches
+    pub fn or(self, other: Filter) -> Filter {
+        match self {
+            Filter::Any(mut filters) => {
+                filters.push(other);
+                Filter::Any(filters)
+            }
+            filter => Filter::Any(vec![filter, other]),
+        }
+    }
+
+    /// Whether the entry described by `metadata` is selected
+    ///
+    /// `metadata` describes a symbolic link itself when links are not
+    /// followed.
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        let modified = || metadata.modified().ok();
+        match self {
+            Filter::NewerThan(time) => modified().is_some_and(|modified| modified > *time),
+            Filter::OlderThan(time) => modified().is_some_and(|modified| modified < *time),
+            Filter::MinSize(size) => metadata.len() >= *size,
+            Filter::MaxSize(size) => metadata.len() <= *size,
+            Filter::Type(kind) => {
+                let file_type = metadata.file_type();
+                match kind {
+                    EntryType::File => file_type.is_file(),
+                    EntryType::Dir => file_type.is_dir(),
+                    EntryType::Symlink => file_type.is_symlink(),
+                }
+            }
+            Filter::All(filters) => filters.iter().all(|filter| filter.matches(metadata)),
+            Filter::Any(filters) => filters.iter().any(|filter| filter.matches(metadata)),
+            Filter::Not(filter) => !filter.matches(metadata),
+        }
+    }
+
+    /// Whether the filter asks for entries of type `kind`
+    pub(crate) fn names(&self, kind: EntryType) -> bool {
+        match self {
+            Filter::Type(named) => *named == kind,
+            Filter::All(filters) | Filter::Any(filters) => {
+                filters.iter().any(|filter| filter.names(kind))
+            }
+            Filter::Not(filter) => filter.names(kind),
+            _ => false,
+        }
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        match self {
+            Filter::Not(filter) => *filter,
+            filter => Filter::Not(Box::new(filter)),
+        }
+    }
+}
+
+/// Parses a point in time given as an age, such as `30m`, `12h`, `2d` or
+/// `1w` before now, or as a local date (`2026-01-01`, optionally followed
+/// by ` 12:00:00` or `T12:00:00`)
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return local_time(date.and_hms_opt(0, 0, 0).unwrap_or_default(), s);
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return local_time(time, s);
+        }
+    }
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (count, unit) = s.split_at(split);
+    let count: u64 = count
+        .parse()
+        .map_err(|_| format!("Invalid time '{}' (expected e.g. 2d or 2026-01-01)", s))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 24 * 3600,
+        "w" => 7 * 24 * 3600,
+        _ => {
+            return Err(format!(
+                "Unknown time unit '{}' (expected s, m, h, d or w)",
+                unit
+            ))
+        }
+    };
+
+    count
+        .checked_mul(seconds)
+        .and_then(|seconds| SystemTime::now().checked_sub(Duration::from_secs(seconds)))
+        .ok_or_else(|| format!("Time '{}' is too far back", s))
+}
+
+fn local_time(time: NaiveDateTime, s: &str) -> Result<SystemTime, String> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or_else(|| format!("Time '{}' does not exist in the local time zone", s))
+}
+
+/// Parses a size such as `512`, `10K`, `10M` or `1G`; the suffixes are
+/// powers of 1024 and may be followed by `B` or `iB`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (count, unit) = s.split_at(split);
+    let count: u64 = count
+        .parse()
+        .map_err(|_| format!("Invalid size '{}' (expected e.g. 10M)", s))?;
+
+    let unit = unit.to_uppercase();
+    let unit = unit.trim_end_matches("IB").trim_end_matches('B');
+    let shift = match unit {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => {
+            return Err(format!(
+                "Unknown size unit '{}' (expected K, M, G or T)",
+                unit
+            ))
+        }
+    };
+
+    count
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("Size '{}' is too large", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let mut rules = FilterRules::new();
+        rules.include("keep.log").unwrap();
+        rules.exclude("*.log").unwrap();
+        rules.exclude("target/").unwrap();
+        rules.exclude("/build").unwrap();
+
+        assert!(rules.excludes(Path::new("a/b/debug.log"), false));
+        assert!(!rules.excludes(Path::new("a/keep.log"), false));
+        assert!(rules.excludes(Path::new("crates/x/target"), true));
+        assert!(!rules.excludes(Path::new("target"), false));
+        assert!(rules.excludes(Path::new("build"), true));
+        assert!(!rules.excludes(Path::new("src/build"), true));
+        assert!(!rules.excludes(Path::new("src/main.rs"), false));
+        assert!(!FilterRules::new().excludes(Path::new("a.log"), false));
+    }
+
+    #[test]
+    fn test_rules_from_file() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("rules");
+        fs::write(&path, "# project\n+ *.rs\n\n- *\n").unwrap();
+
+        let mut rules = FilterRules::new();
+        rules.add_file(&path).unwrap();
+        assert!(!rules.excludes(Path::new("src/lib.rs"), false));
+        assert!(rules.excludes(Path::new("README.md"), false));
+
+        fs::write(&path, "*.rs\n").unwrap();
+        let error = rules.add_file(&path).unwrap_err();
+        assert!(error.to_string().contains("line 1"));
+    }
+
+    #[test]
+    fn test_entry_type_from_str() {
+        assert_eq!("f".parse::<EntryType>(), Ok(EntryType::File));
+        assert_eq!("Dir".parse::<EntryType>(), Ok(EntryType::Dir));
+        assert_eq!("l".parse::<EntryType>(), Ok(EntryType::Symlink));
+        assert!("p".parse::<EntryType>().is_err());
+    }
+
+    #[test]
+    fn test_parse_time_and_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10M"), Ok(10 << 20));
+        assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+        assert_eq!(parse_size("2kb"), Ok(2048));
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("M").is_err());
+
+        let parsed = parse_time("2d").unwrap();
+        let two_days = SystemTime::now() - Duration::from_secs(2 * 24 * 3600);
+        assert!(parsed <= two_days && parsed > two_days - Duration::from_secs(60));
+        assert!(parse_time("2026-01-01").unwrap() < parse_time("2026-01-01 12:00:00").unwrap());
+        assert!(parse_time("2y").is_err());
+    }
+
+    #[test]
+    fn test_filters_compose() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("data");
+        fs::write(&path, vec![0u8; 2048]).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        let recent = Filter::NewerThan(hour_ago);
+        assert!(recent.matches(&metadata));
+        assert!(!(!recent.clone()).matches(&metadata));
+
+        let small_file = Filter::Type(EntryType::File).and(Filter::MaxSize(1024));
+        assert!(!small_file.matches(&metadata));
+        assert!(small_file.or(Filter::MinSize(2048)).matches(&metadata));
+        assert!(!Filter::Type(EntryType::Dir).matches(&metadata));
+        assert!(Filter::OlderThan(hour_ago).or(recent).matches(&metadata));
+    }
+}
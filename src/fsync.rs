@@ -0,0 +1,107 @@
+// File: src\fsync.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Durability of the files written by the native engine
+// License: MIT
+
+//! Durability
+//!
+//! The native engine writes every file to a temporary name in the
+//! destination directory and renames it into place once complete, so that
+//! readers never see a truncated file. [`FsyncMode`] decides whether the
+//! data and the rename are also flushed to the device before moving on.
+
+use crate::error::XcomError;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// What is flushed to the device when a file is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncMode {
+    /// Leave it to the operating system
+    #[default]
+    None,
+    /// The data of every file, before it is renamed into place
+    File,
+    /// The directory holding every file, after it was renamed into place
+    Dir,
+    /// Both
+    All,
+}
+
+impl FsyncMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FsyncMode::None => "none",
+            FsyncMode::File => "file",
+            FsyncMode::Dir => "dir",
+            FsyncMode::All => "all",
+        }
+    }
+
+    /// Whether file data is flushed before the rename
+    pub fn syncs_files(&self) -> bool {
+        matches!(self, FsyncMode::File | FsyncMode::All)
+    }
+
+    /// Whether directories are flushed after the rename
+    pub fn syncs_dirs(&self) -> bool {
+        matches!(self, FsyncMode::Dir | FsyncMode::All)
+    }
+}
+
+impl fmt::Display for FsyncMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FsyncMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(FsyncMode::None),
+            "file" => Ok(FsyncMode::File),
+            "dir" => Ok(FsyncMode::Dir),
+            "all" => Ok(FsyncMode::All),
+            _ => Err(format!(
+                "Unknown fsync mode '{}' (expected none, file, dir or all)",
+                s
+            )),
+        }
+    }
+}
+
+/// Flushes the directory holding `path`, making a rename into it durable
+#[cfg(unix)]
+pub(crate) fn sync_parent(path: &Path) -> Result<(), XcomError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    std::fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|e| XcomError::io("flush", dir, e))
+}
+
+/// Directory entries are written through on Windows
+#[cfg(not(unix))]
+pub(crate) fn sync_parent(_path: &Path) -> Result<(), XcomError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!("all".parse::<FsyncMode>(), Ok(FsyncMode::All));
+        assert!("DIR".parse::<FsyncMode>().unwrap().syncs_dirs());
+        assert!(!FsyncMode::Dir.syncs_files());
+        assert!("always".parse::<FsyncMode>().is_err());
+    }
+}
@@ -0,0 +1,143 @@
+// File: src\gitignore.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Ignore files of git repositories among the sources
+// License: MIT
+
+//! Ignore files
+//!
+//! With [`OperationOptions::respect_gitignore`](crate::OperationOptions::respect_gitignore),
+//! sources inside a git repository are matched against its ignore files as
+//! git does: the `.gitignore` of every directory from the repository root
+//! down to the source, the deepest one deciding, and `.git/info/exclude`
+//! below them. A `.ignore` file takes precedence over the `.gitignore` next
+//! to it. Paths outside of a repository are never ignored.
+
+use crate::logs;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Ignore files read so far, by directory
+#[derive(Debug, Default)]
+pub(crate) struct IgnoreFiles {
+    /// Rules of each directory; `None` where it has no ignore file
+    rules: RefCell<HashMap<PathBuf, Option<Gitignore>>>,
+    /// Root of the repository holding each directory, if any
+    repositories: RefCell<HashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl IgnoreFiles {
+    pub(crate) fn new() -> Self {
+        IgnoreFiles::default()
+    }
+
+    /// Whether git ignores `path`
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(path) = std::path::absolute(path) else {
+            return false;
+        };
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+        let Some(repository) = self.repository(parent) else {
+            return false;
+        };
+
+        for dir in parent.ancestors() {
+            self.load(dir, &repository);
+            let rules = self.rules.borrow();
+            if let Some(Some(rules)) = rules.get(dir) {
+                match rules.matched_path_or_any_parents(&path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+
+            if dir == repository {
+                break;
+            }
+        }
+        false
+    }
+
+    /// Root of the repository `dir` belongs to
+    fn repository(&self, dir: &Path) -> Option<PathBuf> {
+        if let Some(repository) = self.repositories.borrow().get(dir) {
+            return repository.clone();
+        }
+
+        let repository = dir
+            .ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())
+            .map(Path::to_path_buf);
+        self.repositories
+            .borrow_mut()
+            .insert(dir.to_path_buf(), repository.clone());
+        repository
+    }
+
+    /// Reads the ignore files of `dir`, once
+    fn load(&self, dir: &Path, repository: &Path) {
+        if self.rules.borrow().contains_key(dir) {
+            return;
+        }
+
+        // Later files take precedence
+        let mut files = Vec::new();
+        if dir == repository {
+            files.push(dir.join(".git").join("info").join("exclude"));
+        }
+        files.push(dir.join(".gitignore"));
+        files.push(dir.join(".ignore"));
+
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for file in files.iter().filter(|file| file.is_file()) {
+            found = true;
+            // Like git, keep the lines that could be read
+            if let Some(error) = builder.add(file) {
+                logs(&format!("WARNING: {}: {}", file.display(), error));
+            }
+        }
+
+        let rules = if found { builder.build().ok() } else { None };
+        self.rules.borrow_mut().insert(dir.to_path_buf(), rules);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ignore_files_of_repository() {
+        let tmp = tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(repo.join(".git/info")).unwrap();
+        fs::create_dir_all(repo.join("src/gen")).unwrap();
+        fs::write(repo.join(".git/info/exclude"), "secret.txt\n").unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(repo.join("src/.gitignore"), "!keep.log\ngen/\n").unwrap();
+        fs::write(repo.join("src/.ignore"), "scratch.rs\n").unwrap();
+
+        let ignored = IgnoreFiles::new();
+        assert!(ignored.is_ignored(&repo.join("target"), true));
+        assert!(ignored.is_ignored(&repo.join("target/debug/app"), false));
+        assert!(ignored.is_ignored(&repo.join("debug.log"), false));
+        assert!(!ignored.is_ignored(&repo.join("src/keep.log"), false));
+        assert!(ignored.is_ignored(&repo.join("src/gen"), true));
+        assert!(ignored.is_ignored(&repo.join("src/scratch.rs"), false));
+        assert!(ignored.is_ignored(&repo.join("secret.txt"), false));
+        assert!(!ignored.is_ignored(&repo.join("src/main.rs"), false));
+
+        // Outside of the repository the files mean nothing
+        fs::write(tmp.path().join(".gitignore"), "*\n").unwrap();
+        assert!(!ignored.is_ignored(&tmp.path().join("other.txt"), false));
+    }
+}
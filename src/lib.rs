@@ -7,14 +7,61 @@
 //! xcom - Windows File Operations Utility
 //!
 //! A professional utility for performing file copy and move operations
-//! using Windows Shell APIs with comprehensive logging.
+//! using Windows Shell APIs with comprehensive logging. On other platforms
+//! the same operations are carried out by the [`native`] engine.
+
+pub mod backend;
+pub mod cancel;
+pub mod cli;
+pub mod conflict;
+pub mod error;
+pub mod filter;
+pub mod fsync;
+mod gitignore;
+pub mod native;
+pub mod options;
+mod parallel;
+mod pattern;
+mod plan;
+pub mod preserve;
+pub mod progress;
+pub mod reflink;
+pub mod report;
+mod resume;
+pub mod sparse;
+pub mod symlink;
+pub mod verify;
+
+pub use backend::{default_backend, FileOpBackend, Transfer};
+pub use cancel::CancellationToken;
+
+use backend::{DryRunBackend, OperationContext};
+use progress::ProgressTracker;
+pub use conflict::ConflictPolicy;
+pub use error::{FileFailure, XcomError};
+pub use filter::{EntryType, Filter, FilterRules, RuleKind};
+pub use fsync::FsyncMode;
+pub use options::OperationOptions;
+pub use preserve::Preserve;
+pub use reflink::ReflinkMode;
+pub use report::{CopyStrategy, DirReport, ItemNotes, ItemReport, OperationReport, Outcome};
+pub use sparse::SparseMode;
+pub use symlink::SymlinkMode;
+pub use verify::HashAlgorithm;
+
+use conflict::{ConflictResolver, Resolution};
+use gitignore::IgnoreFiles;
+use pattern::Exclusions;
+use plan::Plan;
 
 use chrono::Local;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::time::{Instant, SystemTime};
 
 #[cfg(windows)]
 use std::ffi::OsStr;
@@ -23,7 +70,10 @@ use std::os::windows::ffi::OsStrExt;
 #[cfg(windows)]
 use winapi::shared::windef::HWND;
 #[cfg(windows)]
-use winapi::um::shellapi::{SHFileOperationW, SHFILEOPSTRUCTW, FO_COPY, FO_MOVE, FOF_NOCONFIRMMKDIR};
+use winapi::um::shellapi::{
+    SHFileOperationW, FILEOP_FLAGS, SHFILEOPSTRUCTW, FO_COPY, FO_MOVE, FOF_MULTIDESTFILES,
+    FOF_NOCONFIRMATION, FOF_NOCONFIRMMKDIR,
+};
 
 const LOG_FILENAME: &str = "xcom.log";
 
@@ -39,25 +89,52 @@ pub fn get_log_path() -> PathBuf {
     PathBuf::from(LOG_FILENAME)
 }
 
+thread_local! {
+    /// Log entries held back by [`capture_logs`] on this thread
+    static CAPTURED_LOGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
 /// Writes a log entry with timestamp
 pub fn logs(data: &str) {
-    let log_path = get_log_path();
-    
+    let timestamp = Local::now().format("%d-%m-%Y %H:%M:%S");
+    let entry = format!("{} {}\n", timestamp, data);
+
+    let entry = CAPTURED_LOGS.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(entries) => {
+            entries.push(entry);
+            None
+        }
+        None => Some(entry),
+    });
+    if let Some(entry) = entry {
+        write_logs(&[entry]);
+    }
+}
+
+/// Runs `f`, returning the log entries it wrote instead of writing them
+pub(crate) fn capture_logs<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let previous = CAPTURED_LOGS.with(|captured| captured.replace(Some(Vec::new())));
+    let result = f();
+    let entries = CAPTURED_LOGS.with(|captured| captured.replace(previous));
+    (result, entries.unwrap_or_default())
+}
+
+/// Appends entries returned by [`capture_logs`] to the log file
+pub(crate) fn write_logs(entries: &[String]) {
+    if entries.is_empty() {
+        return;
+    }
+
     if let Ok(mut file) = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(&log_path)
+        .open(get_log_path())
     {
-        let timestamp = Local::now().format("%d-%m-%Y %H:%M:%S");
-        let _ = writeln!(file, "{} {}", timestamp, data);
+        // A single write, so that entries of other threads do not interleave
+        let _ = file.write_all(entries.concat().as_bytes());
     }
 }
 
-#[cfg(windows)]
-fn to_wide_string(s: &str) -> Vec<u16> {
-    OsStr::new(s).encode_wide().chain(Some(0)).collect()
-}
-
 #[cfg(windows)]
 fn to_double_null_wide(paths: &[PathBuf]) -> Vec<u16> {
     let mut result = Vec::new();
@@ -71,7 +148,7 @@ fn to_double_null_wide(paths: &[PathBuf]) -> Vec<u16> {
 }
 
 /// File operation type
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileOperation {
     Copy,
     Move,
@@ -98,16 +175,48 @@ impl FileOperation {
 ///
 /// * `Ok(true)` - Operation completed successfully
 /// * `Ok(false)` - Operation was aborted by user
-/// * `Err(String)` - Operation failed with error message
+/// * `Err(XcomError)` - Operation failed
 #[cfg(windows)]
 pub fn win32_shell_operation(
     sources: Vec<PathBuf>,
     dest: &Path,
     operation: FileOperation,
-) -> Result<bool, String> {
+) -> Result<bool, XcomError> {
+    shell_file_operation(&sources, &[dest.to_path_buf()], operation, FOF_NOCONFIRMMKDIR)
+}
+
+/// Performs a Windows shell file operation with one target path per source
+#[cfg(windows)]
+pub(crate) fn win32_shell_transfer(
+    transfers: &[Transfer],
+    operation: FileOperation,
+) -> Result<bool, XcomError> {
+    if transfers.is_empty() {
+        return Ok(true);
+    }
+
+    let sources: Vec<PathBuf> = transfers.iter().map(|t| t.source.clone()).collect();
+    let targets: Vec<PathBuf> = transfers.iter().map(|t| t.target.clone()).collect();
+
+    shell_file_operation(
+        &sources,
+        &targets,
+        operation,
+        // Conflicts are resolved before the shell gets to see them
+        FOF_MULTIDESTFILES | FOF_NOCONFIRMATION | FOF_NOCONFIRMMKDIR,
+    )
+}
+
+#[cfg(windows)]
+fn shell_file_operation(
+    sources: &[PathBuf],
+    targets: &[PathBuf],
+    operation: FileOperation,
+    flags: FILEOP_FLAGS,
+) -> Result<bool, XcomError> {
     unsafe {
-        let src_wide = to_double_null_wide(&sources);
-        let dest_wide = to_wide_string(&dest.to_string_lossy());
+        let src_wide = to_double_null_wide(sources);
+        let dest_wide = to_double_null_wide(targets);
 
         let op_type = match operation {
             FileOperation::Copy => FO_COPY,
@@ -119,7 +228,7 @@ pub fn win32_shell_operation(
             wFunc: op_type as u32,
             pFrom: src_wide.as_ptr(),
             pTo: dest_wide.as_ptr(),
-            fFlags: FOF_NOCONFIRMMKDIR,
+            fFlags: flags,
             fAnyOperationsAborted: 0,
             hNameMappings: std::ptr::null_mut(),
             lpszProgressTitle: std::ptr::null(),
@@ -132,22 +241,464 @@ pub fn win32_shell_operation(
         }
 
         if result != 0 {
-            let error_msg = format!("SHFileOperation failed: 0x{:08x}", result);
-            logs(&error_msg);
-            return Err(error_msg);
+            let error = XcomError::Shell { code: result };
+            logs(&error.to_string());
+            return Err(error);
         }
 
         Ok(true)
     }
 }
 
+/// Performs a file operation (copy or move) with the native engine
+///
+/// Platforms without the Windows Shell fall back to
+/// [`native::native_operation`], which honours the same contract.
 #[cfg(not(windows))]
 pub fn win32_shell_operation(
-    _sources: Vec<PathBuf>,
-    _dest: &Path,
-    _operation: FileOperation,
-) -> Result<bool, String> {
-    Err("This utility is only supported on Windows".to_string())
+    sources: Vec<PathBuf>,
+    dest: &Path,
+    operation: FileOperation,
+) -> Result<bool, XcomError> {
+    native::native_operation(&sources, dest, operation)
+}
+
+/// Expands `paths` into a plan placing each of them inside `dest`
+fn plan_into_dir(mut plan: Plan, paths: &[PathBuf], dest: &Path) -> Result<Plan, XcomError> {
+    for path in paths {
+        plan.add(Transfer::into_dir(path, dest)?);
+    }
+    Ok(plan)
+}
+
+/// Empty plan of `operation` into `dest`, following `options`
+fn new_plan(dest: &Path, operation: FileOperation, options: &OperationOptions) -> Plan {
+    let mut plan = Plan::new(plan_symlinks(operation, options));
+    plan.root = dest.to_path_buf();
+    plan.filter = options.filter.clone();
+    plan.select = options.select.clone();
+    plan.moves_dirs = operation == FileOperation::Move;
+    if options.respect_gitignore {
+        plan.ignored = Some(IgnoreFiles::new());
+    }
+    plan
+}
+
+/// Symlink mode used to plan `operation`
+///
+/// A move takes links along as they are, as a rename would.
+fn plan_symlinks(operation: FileOperation, options: &OperationOptions) -> SymlinkMode {
+    match (operation, options.symlinks) {
+        (FileOperation::Move, SymlinkMode::Follow) => SymlinkMode::Copy,
+        (_, symlinks) => symlinks,
+    }
+}
+
+/// The error of the directory that could not be created at or above
+/// `target`, among `blocked`
+fn blocked_by<'a>(blocked: &'a [(PathBuf, XcomError)], target: &Path) -> Option<&'a XcomError> {
+    blocked
+        .iter()
+        .find(|(dir, _)| target.starts_with(dir))
+        .map(|(_, error)| error)
+}
+
+/// Resolves conflicts, hands the planned transfers to the backend and
+/// reports the outcome of every file
+fn execute_plan(
+    mut plan: Plan,
+    dest: &Path,
+    operation: FileOperation,
+    backend: &dyn FileOpBackend,
+    options: &OperationOptions,
+) -> Result<OperationReport, XcomError> {
+    let op_str = operation.as_str();
+    let started = Instant::now();
+    let started_at = SystemTime::now();
+    let mut report = OperationReport::new(operation);
+
+    // A dry run goes through the same steps, recorded instead of performed
+    let dry_run = DryRunBackend::new();
+    let backend: &dyn FileOpBackend = if options.dry_run {
+        report.dry_run = true;
+        &dry_run
+    } else {
+        backend
+    };
+
+    plan.prune_dirs();
+
+    // Read now, as moving files out of a directory changes its times
+    let dir_metadata: Vec<_> = plan
+        .dirs
+        .iter()
+        .map(|dir| std::fs::metadata(&dir.source))
+        .collect();
+
+    // Directories first, so that empty ones are replicated as well. One
+    // that cannot be created fails everything planned below it.
+    let mut blocked = Vec::new();
+    if let Err(e) = backend.create_dir(dest) {
+        blocked.push((dest.to_path_buf(), e));
+    }
+    let mut failed_dirs = Vec::new();
+    for dir in &plan.dirs {
+        if blocked_by(&blocked, &dir.target).is_some() {
+            continue;
+        }
+        if let Err(e) = backend.create_dir(&dir.target) {
+            blocked.push((dir.target.clone(), e.clone()));
+            failed_dirs.push((dir.clone(), e));
+        }
+    }
+
+    let transferred = match operation {
+        FileOperation::Copy => Outcome::Copied,
+        FileOperation::Move => Outcome::Moved,
+    };
+
+    let mut resolver = ConflictResolver::new(options.conflict);
+    let mut transfers = Vec::new();
+    // Index in `report.items` of every entry in `transfers`
+    let mut slots = Vec::new();
+    // (dev, inode) -> index of the item copied first, and the items to link
+    // to it once it is in place
+    let mut link_groups = HashMap::new();
+    let mut hard_links = Vec::new();
+
+    for file in plan.files {
+        // The partial copy left by an interrupted run is not a conflict
+        let resolution = if let Some(error) = blocked_by(&blocked, &file.target) {
+            Err(error.clone())
+        } else if options.resume && resume::has_journal(&file.target) {
+            Ok(Resolution::Overwrite)
+        } else {
+            resolver.resolve(&file.source, &file.target)
+        };
+        let replaced = matches!(resolution, Ok(Resolution::Overwrite));
+
+        let (destination, outcome) = match resolution {
+            Ok(Resolution::Create) | Ok(Resolution::Overwrite) => {
+                (file.target.clone(), transferred.clone())
+            }
+            Ok(Resolution::Rename(target)) => {
+                logs(&format!(
+                    "{}: Renamed \"{}\" --> \"{}\"",
+                    op_str,
+                    file.source.display(),
+                    target.display()
+                ));
+                (target, Outcome::Renamed)
+            }
+            Ok(Resolution::Skip) => {
+                logs(&format!(
+                    "{}: Skipped \"{}\" (\"{}\" exists)",
+                    op_str,
+                    file.source.display(),
+                    file.target.display()
+                ));
+                (file.target.clone(), Outcome::Skipped)
+            }
+            Err(e) => {
+                logs(&e.to_string());
+                (file.target.clone(), Outcome::Failed(e))
+            }
+        };
+
+        // Later names of an inode copied earlier become links to its copy
+        let copies_links = options.hard_links && operation == FileOperation::Copy;
+        let leader = if copies_links && outcome.is_transferred() {
+            inode_key(&file.source).and_then(|key| {
+                let first = *link_groups.entry(key).or_insert(report.items.len());
+                (first != report.items.len()).then_some(first)
+            })
+        } else {
+            None
+        };
+
+        let bytes = if let Some(first) = leader {
+            hard_links.push((report.items.len(), first));
+            0
+        } else if outcome.is_transferred() {
+            slots.push(report.items.len());
+            transfers.push(Transfer::new(&file.source, &destination));
+            let metadata = match plan.symlinks {
+                SymlinkMode::Follow => std::fs::metadata(&file.source),
+                _ => std::fs::symlink_metadata(&file.source),
+            };
+            match metadata {
+                // A directory moved whole
+                Ok(m) if m.is_dir() => native::tree_size(&file.source),
+                Ok(m) => m.len(),
+                Err(_) => 0,
+            }
+        } else {
+            0
+        };
+
+        report.items.push(ItemReport {
+            source: file.source,
+            destination,
+            outcome,
+            replaced,
+            bytes,
+            lost_attributes: Vec::new(),
+            linked_to: leader.map(|first| report.items[first].destination.clone()),
+            strategy: None,
+        });
+    }
+
+    // Failed directories with nothing planned below are reported themselves
+    for (dir, error) in failed_dirs {
+        if report.items.iter().any(|item| item.destination.starts_with(&dir.target)) {
+            continue;
+        }
+        logs(&error.to_string());
+        report.items.push(ItemReport {
+            source: dir.source,
+            destination: dir.target,
+            outcome: Outcome::Failed(error),
+            replaced: false,
+            bytes: 0,
+            lost_attributes: Vec::new(),
+            linked_to: None,
+            strategy: None,
+        });
+    }
+
+    for link in plan.skipped {
+        logs(&format!(
+            "{}: Skipped link \"{}\"",
+            op_str,
+            link.source.display()
+        ));
+        report.items.push(ItemReport {
+            source: link.source,
+            destination: link.target,
+            outcome: Outcome::Skipped,
+            replaced: false,
+            bytes: 0,
+            lost_attributes: Vec::new(),
+            linked_to: None,
+            strategy: None,
+        });
+    }
+
+    for link in &plan.dangling {
+        logs(&format!("WARNING: dangling link \"{}\"", link.display()));
+    }
+    for link in &plan.loops {
+        logs(&format!(
+            "WARNING: link \"{}\" points to a parent directory, not followed",
+            link.display()
+        ));
+    }
+    report.dangling_links = plan.dangling;
+    report.link_loops = plan.loops;
+
+    let files_str: Vec<String> = transfers
+        .iter()
+        .map(|t| t.source.to_string_lossy().to_string())
+        .collect();
+
+    let log_msg = format!(
+        "{}: \"{}\" --> \"{}\"",
+        op_str,
+        files_str.join("; "),
+        dest.display()
+    );
+    logs(&log_msg);
+
+    let bytes_total = slots.iter().map(|&slot| report.items[slot].bytes).sum();
+    let progress = ProgressTracker::new(
+        options.progress.clone(),
+        transfers.len() as u64,
+        bytes_total,
+    );
+    let notes = ItemNotes::new();
+    let context = OperationContext {
+        options,
+        progress: &progress,
+        notes: &notes,
+    };
+
+    let result = backend.execute(&transfers, operation, &context);
+    progress.finish();
+
+    for &slot in &slots {
+        notes.fill(&mut report.items[slot]);
+    }
+
+    if matches!(result, Ok(true) | Err(XcomError::Failures(_))) {
+        // A move keeps everything, as a rename of the whole tree would
+        let preserve = match operation {
+            FileOperation::Copy => options.preserve,
+            FileOperation::Move => Preserve::ALL,
+        };
+        // Deepest directories first, so that setting a directory's times
+        // comes after everything written inside it
+        for (dir, metadata) in plan.dirs.iter().zip(&dir_metadata).rev() {
+            if blocked_by(&blocked, &dir.target).is_some() {
+                continue;
+            }
+            if let Ok(metadata) = metadata {
+                let (lost_attributes, error) =
+                    match backend.copy_dir_metadata(dir, metadata, preserve) {
+                        Ok(lost) => (lost, None),
+                        Err(e) => {
+                            logs(&e.to_string());
+                            (Vec::new(), Some(e))
+                        }
+                    };
+                if !lost_attributes.is_empty() || error.is_some() {
+                    report.dirs.push(DirReport {
+                        destination: dir.target.clone(),
+                        lost_attributes,
+                        error,
+                    });
+                }
+            }
+        }
+    }
+
+    let failures = match &result {
+        Err(XcomError::Failures(failures)) | Err(XcomError::Aborted(failures)) => failures.clone(),
+        _ => Vec::new(),
+    };
+
+    match result {
+        Ok(true) | Err(XcomError::Failures(_)) => {
+            if operation == FileOperation::Move {
+                // Deepest directories come last in the plan; those holding
+                // a directory that could not be created keep its contents,
+                // as do those holding what failed or was left out
+                for dir in plan.dirs.iter().rev() {
+                    let keeps_contents = blocked.iter().any(|(blocked, _)| {
+                        blocked.starts_with(&dir.target) || dir.target.starts_with(blocked)
+                    });
+                    if keeps_contents {
+                        continue;
+                    }
+                    match backend.remove_dir(&dir.source) {
+                        Err(e) if e.kind() != Some(std::io::ErrorKind::DirectoryNotEmpty) => {
+                            logs(&e.to_string())
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(false) | Err(XcomError::Aborted(_)) => {
+            // Only the filesystem knows how far the backend got
+            report.aborted = true;
+            for (transfer, &slot) in transfers.iter().zip(&slots) {
+                let replaced = report.items[slot].replaced;
+                if !reached_target(transfer, operation, replaced, started_at) {
+                    report.items[slot].outcome = Outcome::Skipped;
+                    report.items[slot].bytes = 0;
+                }
+            }
+        }
+        Err(e) => {
+            logs(&e.to_string());
+            for &slot in &slots {
+                report.items[slot].outcome = Outcome::Failed(e.clone());
+                report.items[slot].bytes = 0;
+            }
+        }
+    }
+
+    for failure in failures {
+        let position = transfers
+            .iter()
+            .position(|t| t.source == failure.source && t.target == failure.target);
+        if let Some(position) = position {
+            let item = &mut report.items[slots[position]];
+            item.outcome = Outcome::Failed(failure.error);
+            item.bytes = 0;
+        }
+    }
+
+    for (index, first) in hard_links {
+        let original = report.items[first].destination.clone();
+        let original_copied = report.items[first].outcome.is_transferred();
+        let item = &mut report.items[index];
+
+        if report.aborted {
+            item.outcome = Outcome::Skipped;
+        } else if !original_copied {
+            item.outcome = Outcome::Failed(XcomError::invalid(
+                &item.source,
+                format!("hard link target {} was not copied", original.display()),
+            ));
+        } else if let Err(e) = backend.hard_link(&original, &item.destination) {
+            logs(&e.to_string());
+            item.outcome = Outcome::Failed(e);
+        }
+    }
+
+    report.elapsed = started.elapsed();
+    logs(&format!("{}: {}", op_str, report));
+
+    Ok(report)
+}
+
+/// Tells whether an aborted backend got `transfer` to its target
+///
+/// A file that replaced an existing one only counts when the target
+/// changed after the operation started.
+fn reached_target(
+    transfer: &Transfer,
+    operation: FileOperation,
+    replaced: bool,
+    started_at: SystemTime,
+) -> bool {
+    let target = match std::fs::symlink_metadata(&transfer.target) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+    // Kept to be resumed, not complete
+    if resume::has_journal(&transfer.target) {
+        return false;
+    }
+
+    match operation {
+        FileOperation::Move => std::fs::symlink_metadata(&transfer.source).is_err(),
+        FileOperation::Copy if replaced => changed_at(&target).is_some_and(|at| at >= started_at),
+        FileOperation::Copy => true,
+    }
+}
+
+/// Identifies the inode behind `path` when it has several hard links
+#[cfg(unix)]
+fn inode_key(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    (metadata.is_file() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode_key(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// Last time the file's contents or metadata changed
+///
+/// On Unix this is the inode change time, which copying timestamps along
+/// with the data cannot move back.
+fn changed_at(metadata: &std::fs::Metadata) -> Option<SystemTime> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let seconds = metadata.ctime().max(0) as u64;
+        let offset = std::time::Duration::new(seconds, metadata.ctime_nsec() as u32);
+        Some(SystemTime::UNIX_EPOCH + offset)
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.modified().ok()
+    }
 }
 
 /// Performs file operation on directory contents
@@ -158,12 +709,21 @@ pub fn win32_shell_operation(
 /// * `dest` - Destination directory path
 /// * `recursive` - Whether to include subdirectories recursively
 /// * `operation` - Type of operation (Copy or Move)
+/// * `backend` - Backend carrying out the operation
+/// * `options` - Conflict policy and other settings
+///
+/// # Returns
+///
+/// * `Ok(OperationReport)` - Outcome of every item, including failed ones
+/// * `Err(XcomError)` - The operation could not be started
 pub fn perform_operation(
     path: Option<&Path>,
     dest: &Path,
     recursive: bool,
     operation: FileOperation,
-) -> Result<(), String> {
+    backend: &dyn FileOpBackend,
+    options: &OperationOptions,
+) -> Result<OperationReport, XcomError> {
     let source_path = path.unwrap_or_else(|| Path::new("."));
     
     let op_str = operation.as_str();
@@ -172,133 +732,82 @@ pub fn perform_operation(
         op_str, source_path, dest, recursive
     ));
 
-    if !recursive {
+    let plan = if !recursive {
         let list_dir: Vec<PathBuf> = std::fs::read_dir(source_path)
-            .map_err(|e| format!("Failed to read directory: {}", e))?
+            .map_err(|e| XcomError::io("read directory", source_path, e))?
             .filter_map(|entry| entry.ok())
             .map(|entry| entry.path())
             .collect();
 
-        let files_str: Vec<String> = list_dir
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect();
-
-        let log_msg = format!(
-            "{}: \"{}\" --> \"{}\"",
-            op_str,
-            files_str.join("; "),
-            dest.display()
-        );
-        logs(&log_msg);
-
-        match win32_shell_operation(list_dir, dest, operation) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                logs(&e);
-                Err(e)
-            }
-        }
+        plan_into_dir(new_plan(dest, operation, options), &list_dir, dest)?
     } else {
-        let mut list_dir = Vec::new();
-
-        for entry in WalkDir::new(source_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_file() {
-                list_dir.push(entry.path().to_path_buf());
-            }
-        }
-
-        let files_str: Vec<String> = list_dir
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect();
-
-        let log_msg = format!(
-            "{}: \"{}\" --> \"{}\"",
-            op_str,
-            files_str.join("; "),
-            dest.display()
-        );
-        logs(&log_msg);
+        let mut plan = new_plan(dest, operation, options);
+        plan.add_contents(source_path, dest);
+        plan
+    };
 
-        match win32_shell_operation(list_dir, dest, operation) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                logs(&e);
-                Err(e)
-            }
-        }
-    }
+    execute_plan(plan, dest, operation, backend, options)
 }
 
 /// Processes command-line arguments and performs operations
 ///
 /// # Arguments
 ///
-/// * `sources` - Vector of source paths/patterns
+/// * `sources` - Vector of source paths/patterns; a pattern starting with
+///   `!` leaves out the paths it matches, from the other sources and from
+///   the directories below them
 /// * `dest` - Destination directory path
 /// * `operation` - Type of operation (Copy or Move)
+/// * `backend` - Backend carrying out the operation
+/// * `options` - Conflict policy and other settings
+///
+/// # Returns
+///
+/// * `Ok(OperationReport)` - Outcome of every item, including failed ones
+/// * `Err(XcomError)` - The operation could not be started
 pub fn process_sources(
     sources: Vec<String>,
     dest: &Path,
     operation: FileOperation,
-) -> Result<(), String> {
+    backend: &dyn FileOpBackend,
+    options: &OperationOptions,
+) -> Result<OperationReport, XcomError> {
+    let (negated, mut included): (Vec<&String>, Vec<&String>) =
+        sources.iter().partition(|source| source.starts_with('!'));
+    let mut plan = new_plan(dest, operation, options);
+    plan.excluded = Exclusions::new(negated.iter().map(|source| &source[1..]))?;
+
+    // Only exclusions: everything else in the current directory
+    let everything = "*".to_string();
+    if included.is_empty() && !negated.is_empty() {
+        included.push(&everything);
+    }
+
     let mut all_paths = Vec::new();
-    let mut has_wildcard = false;
-
-    for source in &sources {
-        if source == "*" {
-            has_wildcard = true;
-            // Get all files in current directory
-            let list_dir: Vec<PathBuf> = std::fs::read_dir(".")
-                .map_err(|e| format!("Failed to read directory: {}", e))?
-                .filter_map(|entry| entry.ok())
-                .map(|entry| entry.path())
-                .collect();
-            all_paths.extend(list_dir);
-        } else if source.ends_with('*') {
-            has_wildcard = true;
-            let path = if source.len() > 1 {
-                Path::new(&source[..source.len() - 1])
-            } else {
-                Path::new(".")
-            };
-            // Get all files in specified directory
-            let list_dir: Vec<PathBuf> = std::fs::read_dir(path)
-                .map_err(|e| format!("Failed to read directory: {}", e))?
-                .filter_map(|entry| entry.ok())
-                .map(|entry| entry.path())
-                .collect();
-            all_paths.extend(list_dir);
+    for alternative in included.iter().flat_map(|source| pattern::expand_braces(source)) {
+        if pattern::is_pattern(&alternative) {
+            // Wildcards the shell left alone (always the case on Windows);
+            // the destination is never one of its own sources
+            let matches = pattern::expand(&alternative, |path, relative, is_dir| {
+                plan.skips(path, relative, is_dir)
+            })?;
+            all_paths.extend(
+                matches
+                    .into_iter()
+                    .filter(|path| !native::is_same_file(path, dest)),
+            );
         } else {
-            all_paths.push(PathBuf::from(source));
+            all_paths.push(PathBuf::from(alternative));
         }
     }
 
-    // Process ALL files in ONE operation, just like Python!
-    let files_str: Vec<String> = all_paths
-        .iter()
-        .map(|p| p.to_string_lossy().to_string())
-        .collect();
+    // Patterns may overlap; each path is transferred once
+    let mut seen = std::collections::HashSet::new();
+    all_paths.retain(|path| seen.insert(path.clone()));
 
-    let log_msg = format!(
-        "{}: \"{}\" --> \"{}\"",
-        operation.as_str(),
-        files_str.join("; "),
-        dest.display()
-    );
-    logs(&log_msg);
-
-    match win32_shell_operation(all_paths, dest, operation) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            logs(&e);
-            Err(e)
-        }
-    }
+    // Process ALL files in ONE operation, just like Python!
+    let plan = plan_into_dir(plan, &all_paths, dest)?;
+    execute_plan(plan, dest, operation, backend, options)
 }
 
 #[cfg(test)]
@@ -316,4 +825,643 @@ mod tests {
         assert_eq!(FileOperation::Copy.as_str(), "COPY");
         assert_eq!(FileOperation::Move.as_str(), "MOVE");
     }
+
+    #[test]
+    fn test_recursive_operation_preserves_structure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("a")).unwrap();
+        std::fs::create_dir_all(src.join("b")).unwrap();
+        std::fs::create_dir_all(src.join("empty")).unwrap();
+        std::fs::write(src.join("a/same.txt"), "a").unwrap();
+        std::fs::write(src.join("b/same.txt"), "b").unwrap();
+
+        let dest = tmp.path().join("dest");
+        let backend = backend::NativeBackend;
+        let options = OperationOptions::default();
+        let report =
+            perform_operation(Some(&src), &dest, true, FileOperation::Move, &backend, &options)
+                .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(report.transferred(), 2);
+        assert_eq!(report.bytes_transferred(), 2);
+
+        assert_eq!(std::fs::read_to_string(dest.join("a/same.txt")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(dest.join("b/same.txt")).unwrap(), "b");
+        assert!(dest.join("empty").is_dir());
+        assert!(src.exists());
+        assert!(!src.join("a").exists());
+    }
+
+    #[test]
+    fn test_report_records_backend_failures() {
+        let tmp = tempfile::tempdir().unwrap();
+        let present = tmp.path().join("present.txt");
+        std::fs::write(&present, "data").unwrap();
+        let missing = tmp.path().join("missing.txt");
+
+        let sources = vec![
+            present.to_string_lossy().to_string(),
+            missing.to_string_lossy().to_string(),
+        ];
+        let dest = tmp.path().join("dest");
+        let backend = backend::NativeBackend;
+        let options = OperationOptions::default();
+
+        let report =
+            process_sources(sources, &dest, FileOperation::Copy, &backend, &options).unwrap();
+
+        assert_eq!(report.items[0].outcome, Outcome::Copied);
+        assert_eq!(report.items[0].destination, dest.join("present.txt"));
+        assert_eq!(report.items[0].bytes, 4);
+        assert!(matches!(report.items[1].outcome, Outcome::Failed(ref e) if e.is_not_found()));
+        assert!(!report.is_success());
+    }
+
+    #[test]
+    fn test_dry_run_leaves_filesystem_untouched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/a.txt"), "a").unwrap();
+        let dest = tmp.path().join("dest");
+        std::fs::create_dir_all(dest.join("src/sub")).unwrap();
+        std::fs::write(dest.join("src/sub/a.txt"), "old").unwrap();
+
+        let backend = backend::MockBackend::default();
+        let options = OperationOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report =
+            process_sources(sources, &dest, FileOperation::Move, &backend, &options).unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.items[0].action(), "overwrite");
+        assert!(backend.calls().is_empty());
+        assert!(src.join("sub/a.txt").exists());
+        assert_eq!(std::fs::read_to_string(dest.join("src/sub/a.txt")).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_cancelled_operation_reports_nothing_done() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("a.txt"), "new").unwrap();
+        std::fs::write(src.join("b.txt"), "new").unwrap();
+        let dest = tmp.path().join("dest");
+        std::fs::create_dir_all(dest.join("src")).unwrap();
+        std::fs::write(dest.join("src/a.txt"), "old").unwrap();
+
+        let options = OperationOptions::default();
+        options.cancel.cancel();
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        assert!(report.aborted);
+        assert_eq!(report.skipped(), 2);
+        assert_eq!(report.bytes_transferred(), 0);
+        assert_eq!(std::fs::read_to_string(dest.join("src/a.txt")).unwrap(), "old");
+        assert!(!dest.join("src/b.txt").exists());
+    }
+
+    #[test]
+    fn test_copy_preserves_timestamps_of_files_and_directories() {
+        use filetime::FileTime;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/a.txt"), "a").unwrap();
+
+        let mtime = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(src.join("sub/a.txt"), mtime).unwrap();
+        filetime::set_file_mtime(src.join("sub"), mtime).unwrap();
+
+        let options = OperationOptions {
+            preserve: Preserve::ALL,
+            ..Default::default()
+        };
+        let dest = tmp.path().join("dest");
+        let sources = vec![src.to_string_lossy().to_string()];
+        process_sources(
+            sources,
+            &dest,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        for copied in [dest.join("src/sub/a.txt"), dest.join("src/sub")] {
+            let metadata = std::fs::metadata(copied).unwrap();
+            assert_eq!(FileTime::from_last_modification_time(&metadata), mtime);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_report_lists_dangling_and_skipped_links() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("gone", src.join("dangling")).unwrap();
+
+        let options = OperationOptions {
+            symlinks: SymlinkMode::Skip,
+            ..Default::default()
+        };
+        let dest = tmp.path().join("dest");
+        let report = perform_operation(
+            Some(&src),
+            &dest,
+            true,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(report.dangling_links, vec![src.join("dangling")]);
+        assert_eq!(report.transferred(), 1);
+        assert_eq!(report.skipped(), 1);
+        assert!(std::fs::symlink_metadata(dest.join("dangling")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_are_recreated() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("store");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("a.bin"), "data").unwrap();
+        std::fs::hard_link(src.join("a.bin"), src.join("sub/b.bin")).unwrap();
+
+        let options = OperationOptions {
+            hard_links: true,
+            ..Default::default()
+        };
+        let dest = tmp.path().join("dest");
+        let report = perform_operation(
+            Some(&src),
+            &dest,
+            true,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        let a = std::fs::metadata(dest.join("a.bin")).unwrap();
+        let b = std::fs::metadata(dest.join("sub/b.bin")).unwrap();
+        assert_eq!(a.ino(), b.ino());
+        assert_eq!(report.transferred(), 2);
+        assert_eq!(report.bytes_transferred(), 4);
+        assert_eq!(
+            report.items.iter().filter(|i| i.action() == "link").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_process_sources_uses_backend() {
+        let backend = backend::MockBackend::default();
+        let sources = vec!["a.txt".to_string(), "dir/b.txt".to_string()];
+
+        let options = OperationOptions::default();
+        process_sources(sources, Path::new("dest"), FileOperation::Move, &backend, &options)
+            .unwrap();
+
+        let calls = backend.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, FileOperation::Move);
+        assert_eq!(
+            calls[0].1,
+            vec![
+                Transfer::new("a.txt", "dest/a.txt"),
+                Transfer::new("dir/b.txt", "dest/b.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sources_with_braces_and_exclusions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for dir in ["src/cache", "tests", "docs"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for name in ["src/a.rs", "src/a.tmp", "src/cache/b.rs", "tests/t.rs", "docs/d.rs"] {
+            std::fs::write(root.join(name), "x").unwrap();
+        }
+
+        let dest = root.join("dest");
+        let sources = vec![
+            format!("{}/{{src,tests}}", root.display()),
+            "!**/*.tmp".to_string(),
+            "!**/cache".to_string(),
+        ];
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &OperationOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.transferred(), 2);
+        assert!(dest.join("src/a.rs").is_file());
+        assert!(dest.join("tests/t.rs").is_file());
+        assert!(!dest.join("src/a.tmp").exists());
+        assert!(!dest.join("src/cache").exists());
+        assert!(!dest.join("docs").exists());
+    }
+
+    #[test]
+    fn test_pattern_never_matches_destination() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("out")).unwrap();
+        std::fs::write(root.join("out/o.txt"), "o").unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+
+        // The destination spelled differently from the matches
+        let dest = root.join(".").join("out");
+        let sources = vec![format!("{}/*", root.display())];
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Move,
+            &backend::NativeBackend,
+            &OperationOptions::default(),
+        )
+        .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(report.transferred(), 1);
+        assert!(root.join("out/a.txt").is_file());
+        assert!(!root.join("out/out").exists());
+    }
+
+    #[test]
+    fn test_filter_rules_skip_build_outputs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("project");
+        for dir in ["src", "target/debug", "web/node_modules/x"] {
+            std::fs::create_dir_all(src.join(dir)).unwrap();
+        }
+        for name in ["src/main.rs", "build.log", "target/debug/app", "web/node_modules/x/i.js"] {
+            std::fs::write(src.join(name), "x").unwrap();
+        }
+
+        let mut filter = FilterRules::new();
+        for pattern in ["target/", "node_modules/", "*.log"] {
+            filter.exclude(pattern).unwrap();
+        }
+        let options = OperationOptions {
+            filter,
+            ..Default::default()
+        };
+        let dest = tmp.path().join("dest");
+        let report = perform_operation(
+            Some(&src),
+            &dest,
+            true,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(report.transferred(), 1);
+        assert!(dest.join("src/main.rs").is_file());
+        assert!(dest.join("web").is_dir());
+        assert!(!dest.join("web/node_modules").exists());
+        assert!(!dest.join("target").exists());
+        assert!(!dest.join("build.log").exists());
+    }
+
+    #[test]
+    fn test_respect_gitignore_in_walk_and_patterns() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        std::fs::create_dir_all(repo.join("target")).unwrap();
+        std::fs::write(repo.join(".gitignore"), "target/\n.env\n").unwrap();
+        for name in ["src/main.rs", "target/app", ".env"] {
+            std::fs::write(repo.join(name), "x").unwrap();
+        }
+
+        let options = OperationOptions {
+            respect_gitignore: true,
+            ..Default::default()
+        };
+        let dest = tmp.path().join("dest");
+        perform_operation(
+            Some(&repo),
+            &dest,
+            true,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+        assert!(dest.join("src/main.rs").is_file());
+        assert!(!dest.join("target").exists());
+        assert!(!dest.join(".env").exists());
+
+        let dest = tmp.path().join("matches");
+        let sources = vec![format!("{}/*", repo.display())];
+        process_sources(
+            sources,
+            &dest,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+        assert!(dest.join("src/main.rs").is_file());
+        assert!(!dest.join("target").exists());
+        assert!(!dest.join(".env").exists());
+    }
+
+    #[test]
+    fn test_select_files_by_metadata() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("logs");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("small.log"), "x").unwrap();
+        std::fs::write(src.join("sub/large.log"), vec![0u8; 4096]).unwrap();
+
+        let options = OperationOptions {
+            select: Some(Filter::Type(EntryType::File).and(Filter::MinSize(1024))),
+            ..Default::default()
+        };
+        let dest = tmp.path().join("archive");
+        let report = perform_operation(
+            Some(&src),
+            &dest,
+            true,
+            FileOperation::Move,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(report.transferred(), 1);
+        assert!(dest.join("sub/large.log").is_file());
+        assert!(!dest.join("small.log").exists());
+        assert!(src.join("small.log").is_file());
+    }
+
+    #[test]
+    fn test_cancelled_operation_keeps_failures() {
+        use progress::CancelAfter;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let missing = tmp.path().join("missing.bin");
+        let big = tmp.path().join("big.bin");
+        std::fs::write(&big, vec![7u8; 4 << 20]).unwrap();
+
+        let cancel = CancellationToken::new();
+        let options = OperationOptions {
+            progress: Some(std::sync::Arc::new(CancelAfter(cancel.clone(), 0))),
+            cancel,
+            ..Default::default()
+        };
+        let sources = vec![
+            missing.to_string_lossy().to_string(),
+            big.to_string_lossy().to_string(),
+        ];
+        let dest = tmp.path().join("dest");
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Copy,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        assert!(report.aborted);
+        assert!(matches!(report.items[0].outcome, Outcome::Failed(ref e) if e.is_not_found()));
+        assert_eq!(report.items[1].outcome, Outcome::Skipped);
+        assert!(matches!(report.error(), Some(XcomError::Aborted(ref f)) if f.len() == 1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_renames_directory_whole() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/a.txt"), "abc").unwrap();
+        let inode = std::fs::metadata(&src).unwrap().ino();
+        let dest = tmp.path().join("dest");
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Move,
+            &backend::NativeBackend,
+            &OperationOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.items.len(), 1);
+        assert_eq!(report.items[0].outcome, Outcome::Moved);
+        assert_eq!(report.items[0].strategy, Some(CopyStrategy::Rename));
+        assert_eq!(report.bytes_transferred(), 3);
+        assert_eq!(std::fs::metadata(dest.join("src")).unwrap().ino(), inode);
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn test_directory_that_cannot_be_created_fails_its_subtree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        for dir in ["d1", "d2/a/b", "d2/c", "d2/s2/a"] {
+            std::fs::create_dir_all(src.join(dir)).unwrap();
+        }
+        std::fs::write(src.join("d1/x.txt"), "x").unwrap();
+        std::fs::write(src.join("d2/a/b/y.txt"), "y").unwrap();
+        std::fs::write(src.join("d2/c/z.txt"), "z").unwrap();
+        // A file stands where directory "a" has to go
+        let dest = tmp.path().join("dest");
+        std::fs::create_dir_all(dest.join("src/d2")).unwrap();
+        std::fs::write(dest.join("src/d2/a"), "in the way").unwrap();
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Move,
+            &backend::NativeBackend,
+            &OperationOptions::default(),
+        )
+        .unwrap();
+
+        let failed: Vec<PathBuf> = report
+            .failures()
+            .into_iter()
+            .map(|failure| failure.source)
+            .collect();
+        assert_eq!(failed, vec![src.join("d2/a/b/y.txt")]);
+        assert_eq!(report.transferred(), 2);
+        assert!(dest.join("src/d1/x.txt").exists());
+        assert!(dest.join("src/d2/c/z.txt").exists());
+        assert!(dest.join("src/d2/s2/a").is_dir());
+        assert!(src.join("d2/a/b/y.txt").exists());
+    }
+
+    #[test]
+    fn test_report_lists_directories_that_lost_attributes() {
+        struct LossyBackend;
+
+        impl FileOpBackend for LossyBackend {
+            fn name(&self) -> &'static str {
+                "lossy"
+            }
+
+            fn execute(
+                &self,
+                transfers: &[Transfer],
+                operation: FileOperation,
+                context: &OperationContext,
+            ) -> Result<bool, XcomError> {
+                backend::NativeBackend.execute(transfers, operation, context)
+            }
+
+            fn copy_dir_metadata(
+                &self,
+                _dir: &Transfer,
+                _metadata: &std::fs::Metadata,
+                _preserve: Preserve,
+            ) -> Result<Vec<String>, XcomError> {
+                Ok(vec!["user.comment".to_string()])
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/a.txt"), "a").unwrap();
+        let dest = tmp.path().join("dest");
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Copy,
+            &LossyBackend,
+            &OperationOptions::default(),
+        )
+        .unwrap();
+
+        let dirs: Vec<&Path> = report.dirs.iter().map(|d| d.destination.as_path()).collect();
+        assert_eq!(dirs, vec![dest.join("src/sub"), dest.join("src")]);
+        assert_eq!(report.dirs[0].lost_attributes, vec!["user.comment"]);
+        assert!(report.is_success());
+    }
+
+    #[test]
+    fn test_archive_creates_no_empty_directories() {
+        use filetime::FileTime;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("data");
+        for dir in ["old", "new", "mixed", "empty"] {
+            std::fs::create_dir_all(src.join(dir)).unwrap();
+        }
+        for file in ["old/a.txt", "mixed/b.txt", "new/c.txt", "mixed/d.txt"] {
+            std::fs::write(src.join(file), file).unwrap();
+        }
+        let long_ago = FileTime::from_unix_time(1_000_000_000, 0);
+        for file in ["old/a.txt", "mixed/b.txt"] {
+            filetime::set_file_mtime(src.join(file), long_ago).unwrap();
+        }
+
+        let week_ago = SystemTime::now() - std::time::Duration::from_secs(7 * 86400);
+        let options = OperationOptions {
+            select: Some(Filter::OlderThan(week_ago)),
+            ..Default::default()
+        };
+        let dest = tmp.path().join("archive");
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Move,
+            &backend::NativeBackend,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(report.transferred(), 2);
+        assert!(dest.join("data/old/a.txt").is_file());
+        assert!(dest.join("data/mixed/b.txt").is_file());
+        assert!(!dest.join("data/new").exists());
+        assert!(!dest.join("data/empty").exists());
+        for entry in walkdir::WalkDir::new(&dest) {
+            let entry = entry.unwrap();
+            if entry.file_type().is_dir() {
+                assert!(std::fs::read_dir(entry.path()).unwrap().next().is_some());
+            }
+        }
+
+        assert!(!src.join("old").exists());
+        assert!(src.join("mixed/d.txt").is_file());
+        assert!(src.join("new/c.txt").is_file());
+        assert!(src.join("empty").is_dir());
+    }
+
+    #[test]
+    fn test_move_with_failures_removes_emptied_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(src.join("d1")).unwrap();
+        std::fs::create_dir_all(src.join("d2")).unwrap();
+        std::fs::write(src.join("d1/a.txt"), "a").unwrap();
+        std::fs::write(src.join("d2/b.txt"), "b").unwrap();
+        // An existing directory in its place makes b.txt fail
+        let dest = tmp.path().join("dest");
+        std::fs::create_dir_all(dest.join("src/d2/b.txt/inside")).unwrap();
+        let sources = vec![src.to_string_lossy().to_string()];
+
+        let report = process_sources(
+            sources,
+            &dest,
+            FileOperation::Move,
+            &backend::NativeBackend,
+            &OperationOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.failures().len(), 1);
+        assert!(dest.join("src/d1/a.txt").is_file());
+        assert!(!src.join("d1").exists());
+        assert!(src.join("d2/b.txt").is_file());
+    }
 }
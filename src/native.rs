@@ -0,0 +1,1357 @@
+// File: src\native.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Native cross-platform copy/move engine
+// License: MIT
+
+//! Native file operation engine
+//!
+//! A pure Rust implementation of the copy and move operations normally
+//! delegated to `SHFileOperationW`, used on platforms without the Windows
+//! Shell. It follows the same semantics: every source lands inside the
+//! destination directory, directories are copied recursively and the
+//! destination directory is created without confirmation.
+
+use crate::backend::{OperationContext, Transfer};
+use crate::error::{FileFailure, XcomError};
+use crate::fsync;
+use crate::parallel;
+use crate::preserve::{self, Preserve};
+use crate::progress::ProgressTracker;
+use crate::reflink::{self, ReflinkMode};
+use crate::report::{CopyStrategy, ItemNotes};
+use crate::resume::{self, Journal, CHECKPOINT_INTERVAL};
+use crate::sparse::{self, SparseMode};
+use crate::symlink::SymlinkMode;
+use crate::verify;
+use crate::{logs, FileOperation, OperationOptions};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Size of the buffer used to copy file contents
+const COPY_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Granularity at which runs of zeros become holes with `--sparse=always`
+const SPARSE_BLOCK_SIZE: usize = 4096;
+
+/// Longest file name most filesystems accept, in bytes
+const NAME_MAX: usize = 255;
+
+/// Performs a native file operation (copy or move)
+///
+/// # Arguments
+///
+/// * `sources` - Source file or directory paths
+/// * `dest` - Destination directory path (created if missing)
+/// * `operation` - Type of operation (Copy or Move)
+///
+/// # Returns
+///
+/// * `Ok(true)` - Operation completed successfully
+/// * `Ok(false)` - Operation was aborted before completion
+/// * `Err(XcomError)` - Operation failed
+pub fn native_operation(
+    sources: &[PathBuf],
+    dest: &Path,
+    operation: FileOperation,
+) -> Result<bool, XcomError> {
+    fs::create_dir_all(dest).map_err(|e| XcomError::io("create directory", dest, e))?;
+
+    let transfers = sources
+        .iter()
+        .map(|source| Transfer::into_dir(source, dest))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let options = OperationOptions::default();
+    let progress = ProgressTracker::disabled();
+    let notes = ItemNotes::new();
+    let context = OperationContext {
+        options: &options,
+        progress: &progress,
+        notes: &notes,
+    };
+
+    native_transfer(&transfers, operation, &context)
+}
+
+/// Copies or moves every transfer source to its exact target path
+///
+/// Missing parent directories of the targets are created on the way. A
+/// failing transfer does not stop the others; all failures are returned
+/// together as [`XcomError::Failures`]. When the operation is cancelled the
+/// files being written are removed and `Ok(false)` is returned, or
+/// [`XcomError::Aborted`] with the files that failed before.
+///
+/// [`OperationOptions::jobs`] transfers run at once, each worker with its
+/// own copy buffer. Transfers to the same target run one after another on
+/// the same worker, so that the last one wins. The log reads as if they
+/// had all run one after another.
+pub fn native_transfer(
+    transfers: &[Transfer],
+    operation: FileOperation,
+    context: &OperationContext,
+) -> Result<bool, XcomError> {
+    let jobs = match context.options.jobs {
+        0 => parallel::available_jobs(),
+        jobs => jobs,
+    };
+    let cancel = &context.options.cancel;
+
+    // Indexes of the transfers to each target, in plan order
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of = HashMap::new();
+    for (index, transfer) in transfers.iter().enumerate() {
+        let group = *group_of.entry(&transfer.target).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+
+    let group_results = parallel::run_ordered(
+        &groups,
+        jobs,
+        || cancel.is_cancelled(),
+        |group| {
+            group
+                .iter()
+                .map(|&index| {
+                    (!cancel.is_cancelled())
+                        .then(|| transfer_one(&transfers[index], operation, context))
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+
+    let mut results: Vec<Option<Result<(), XcomError>>> = transfers.iter().map(|_| None).collect();
+    for (group, group_result) in groups.iter().zip(group_results) {
+        for (&index, result) in group.iter().zip(group_result.into_iter().flatten()) {
+            results[index] = result;
+        }
+    }
+
+    let mut failures = Vec::new();
+    let mut aborted = false;
+    for (transfer, result) in transfers.iter().zip(results) {
+        match result {
+            None => aborted = true,
+            Some(Ok(())) => {}
+            Some(Err(error)) if error.is_cancelled() => aborted = true,
+            Some(Err(error)) => failures.push(FileFailure {
+                source: transfer.source.clone(),
+                target: transfer.target.clone(),
+                error,
+            }),
+        }
+    }
+
+    match (aborted, failures.is_empty()) {
+        (false, true) => Ok(true),
+        (true, true) => Ok(false),
+        (false, false) => Err(XcomError::Failures(failures)),
+        (true, false) => Err(XcomError::Aborted(failures)),
+    }
+}
+
+/// Copies or moves a single transfer, logging how it failed
+fn transfer_one(
+    transfer: &Transfer,
+    operation: FileOperation,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    let progress = context.progress;
+    progress.start_file(&transfer.source);
+
+    let result = ensure_parent(&transfer.target).and_then(|_| match operation {
+        FileOperation::Copy => copy_item(&transfer.source, &transfer.target, context),
+        FileOperation::Move => move_item(&transfer.source, &transfer.target, context),
+    });
+
+    progress.finish_file();
+
+    match &result {
+        Err(error) if error.is_cancelled() => logs(&format!(
+            "{}: Cancelled at \"{}\"",
+            operation.as_str(),
+            transfer.source.display()
+        )),
+        Err(error) => logs(&error.to_string()),
+        Ok(()) => {}
+    }
+
+    result
+}
+
+/// Copies a file or a directory tree to `target`
+///
+/// Existing files at the destination are overwritten and existing
+/// directories are merged, matching the shell's behaviour with
+/// confirmation disabled. The attributes selected by
+/// [`OperationOptions::preserve`] are carried over, and symbolic links are
+/// handled according to [`OperationOptions::symlinks`].
+pub fn copy_item(
+    source: &Path,
+    target: &Path,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    let options = context.options;
+    copy_tree(source, target, options.preserve, options.symlinks, context)
+}
+
+fn copy_tree(
+    source: &Path,
+    target: &Path,
+    preserve: Preserve,
+    symlinks: SymlinkMode,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    let link_metadata =
+        fs::symlink_metadata(source).map_err(|e| XcomError::io("read", source, e))?;
+
+    if link_metadata.file_type().is_symlink() {
+        match symlinks {
+            SymlinkMode::Copy => {
+                copy_symlink(source, target)?;
+                let lost = preserve::apply(source, &link_metadata, target, preserve)?;
+                context.notes.lost_attributes(target, lost);
+                return Ok(());
+            }
+            SymlinkMode::Skip => return Ok(()),
+            SymlinkMode::Follow => {}
+        }
+    }
+
+    let metadata = fs::metadata(source).map_err(|e| XcomError::io("read", source, e))?;
+
+    if metadata.is_dir() {
+        if is_inside(target, source) {
+            return Err(XcomError::invalid(
+                source,
+                format!("cannot copy a directory into itself ({})", target.display()),
+            ));
+        }
+        copy_dir(source, target, &metadata, preserve, symlinks, context)
+    } else {
+        copy_file(source, target, &metadata, preserve, context)
+    }
+}
+
+/// Moves a file or a directory tree to `target`
+///
+/// A directory moved onto an existing directory is merged into it, and the
+/// emptied source directory is removed afterwards. Items are renamed when
+/// possible; across filesystems they are copied, checked and only then
+/// removed from the source.
+pub fn move_item(
+    source: &Path,
+    target: &Path,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    let metadata = fs::symlink_metadata(source).map_err(|e| XcomError::io("read", source, e))?;
+
+    if metadata.is_dir() && is_inside(target, source) {
+        return Err(XcomError::invalid(
+            source,
+            format!("cannot move a directory into itself ({})", target.display()),
+        ));
+    }
+
+    if metadata.is_dir() && target.is_dir() {
+        for entry in read_dir(source)? {
+            if context.options.cancel.is_cancelled() {
+                return Err(XcomError::Cancelled);
+            }
+
+            let name = entry
+                .file_name()
+                .map(|n| n.to_os_string())
+                .unwrap_or_default();
+            move_item(&entry, &target.join(name), context)?;
+        }
+
+        return fs::remove_dir(source).map_err(|e| XcomError::io("remove directory", source, e));
+    }
+
+    match fs::rename(source, target) {
+        Ok(()) => {
+            context.notes.strategy(target, CopyStrategy::Rename);
+            // A rename moves everything at once; copies count as they go
+            context.progress.add_bytes(tree_size(target));
+            if context.options.fsync.syncs_dirs() {
+                fsync::sync_parent(target)?;
+            }
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            move_across_devices(source, target, &metadata, context)
+        }
+        Err(e) => Err(XcomError::io("move", source, e)),
+    }
+}
+
+/// Moves an item with copy and delete, for targets on another filesystem
+///
+/// The source is removed only once the copy is complete, and verified when
+/// [`OperationOptions::verify`] is set, so a failed or cancelled copy never
+/// loses data.
+fn move_across_devices(
+    source: &Path,
+    target: &Path,
+    metadata: &fs::Metadata,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    // Like mv, keep links as links and every attribute a rename would keep
+    copy_tree(source, target, Preserve::ALL, SymlinkMode::Copy, context)?;
+    check_copy(source, target)?;
+
+    if metadata.is_dir() {
+        fs::remove_dir_all(source).map_err(|e| XcomError::io("remove directory", source, e))
+    } else {
+        fs::remove_file(source).map_err(|e| XcomError::io("remove", source, e))
+    }
+}
+
+/// Makes sure every file and link of `source` reached `target`, files
+/// with the same size
+fn check_copy(source: &Path, target: &Path) -> Result<(), XcomError> {
+    let source_metadata =
+        fs::symlink_metadata(source).map_err(|e| XcomError::io("read", source, e))?;
+    let target_metadata =
+        fs::symlink_metadata(target).map_err(|e| XcomError::io("read", target, e))?;
+
+    if source_metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+
+    if source_metadata.is_dir() {
+        for entry in read_dir(source)? {
+            let name = entry
+                .file_name()
+                .map(|n| n.to_os_string())
+                .unwrap_or_default();
+            check_copy(&entry, &target.join(name))?;
+        }
+        return Ok(());
+    }
+
+    if source_metadata.len() != target_metadata.len() {
+        return Err(XcomError::invalid(
+            target,
+            format!(
+                "copy is {} bytes, source has {}; source kept",
+                target_metadata.len(),
+                source_metadata.len()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Recreates the symbolic link `source` at `target`
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> Result<(), XcomError> {
+    let link = fs::read_link(source).map_err(|e| XcomError::io("read link", source, e))?;
+    if fs::symlink_metadata(target).is_ok() {
+        fs::remove_file(target).map_err(|e| XcomError::io("replace", target, e))?;
+    }
+    std::os::unix::fs::symlink(&link, target).map_err(|e| XcomError::io("create link", target, e))
+}
+
+/// Recreates the symbolic link `source` at `target`
+#[cfg(windows)]
+fn copy_symlink(source: &Path, target: &Path) -> Result<(), XcomError> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    let link = fs::read_link(source).map_err(|e| XcomError::io("read link", source, e))?;
+    if fs::symlink_metadata(target).is_ok() {
+        fs::remove_file(target).map_err(|e| XcomError::io("replace", target, e))?;
+    }
+
+    // Windows links know whether they point to a directory
+    let result = if fs::metadata(source).map(|m| m.is_dir()).unwrap_or(false) {
+        symlink_dir(&link, target)
+    } else {
+        symlink_file(&link, target)
+    };
+    result.map_err(|e| XcomError::io("create link", target, e))
+}
+
+fn copy_file(
+    source: &Path,
+    target: &Path,
+    metadata: &fs::Metadata,
+    preserve: Preserve,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    if is_same_file(source, target) {
+        return Err(XcomError::invalid(source, "cannot copy a file onto itself"));
+    }
+
+    // Written under a temporary name, so that nobody sees it incomplete
+    let partial = partial_path(target);
+    let options = context.options;
+    let cancel = &options.cancel;
+    let journal = (options.resume && metadata.len() > CHECKPOINT_INTERVAL)
+        .then(|| Journal::new(source, target, metadata));
+    let start = match &journal {
+        Some(journal) => resume::resume_offset(journal, source, &partial, cancel)?,
+        None => 0,
+    };
+
+    let mut reader = File::open(source).map_err(|e| XcomError::io("open", source, e))?;
+    let mut writer = if start > 0 {
+        logs(&format!(
+            "COPY: Resuming \"{}\" at {} bytes",
+            target.display(),
+            start
+        ));
+        fs::OpenOptions::new().write(true).open(&partial)
+    } else {
+        File::create(&partial)
+    }
+    .map_err(|e| XcomError::io("create", &partial, e))?;
+    if let Some(journal) = &journal {
+        journal.record(start)?;
+    }
+
+    let resume = journal.as_ref().map(|journal| (journal, start));
+    let copied = match copy_contents(
+        &mut reader,
+        &mut writer,
+        source,
+        target,
+        metadata,
+        resume,
+        context,
+    ) {
+        Err(error)
+            if journal
+                .as_ref()
+                .is_some_and(|j| j.checkpoint(&writer, target).is_ok()) =>
+        {
+            // Keep what was copied for a rerun with --resume
+            return Err(error);
+        }
+        copied => copied.and_then(|strategy| {
+            if let Some(algorithm) = options.verify {
+                verify::verify_copy(source, &partial, &writer, algorithm, cancel)?;
+            }
+            let lost = preserve::apply(source, metadata, &partial, preserve)?;
+            if options.fsync.syncs_files() {
+                writer
+                    .sync_all()
+                    .map_err(|e| XcomError::io("flush", target, e))?;
+            }
+            Ok((strategy, lost))
+        }),
+    };
+
+    drop(writer);
+    // Even without --resume: a journal left by an earlier run no longer
+    // describes the target
+    resume::remove_journal(target);
+    let renamed = copied.and_then(|done| {
+        fs::rename(&partial, target)
+            .map(|_| done)
+            .map_err(|e| XcomError::io("write", target, e))
+    });
+    let (strategy, lost) = match renamed {
+        Ok(done) => done,
+        Err(error) => {
+            // Never leave a half-written or corrupt file behind
+            let _ = fs::remove_file(&partial);
+            return Err(error);
+        }
+    };
+
+    context.notes.strategy(target, strategy);
+    context.notes.lost_attributes(target, lost);
+    if options.fsync.syncs_dirs() {
+        fsync::sync_parent(target)?;
+    }
+    Ok(())
+}
+
+/// Temporary name of a file being written, next to its `target`
+fn partial_path(target: &Path) -> PathBuf {
+    sibling_path(target, ".", ".xcom-tmp")
+}
+
+/// Path next to `target`, named after it between `prefix` and `suffix`
+///
+/// A name that would get too long for the filesystem is shortened, and a
+/// hash of the full name keeps it apart from those it now shares a prefix
+/// with. The same target always gets the same path.
+pub(crate) fn sibling_path(target: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = target.file_name().unwrap_or_default();
+    let mut sibling = OsString::from(prefix);
+
+    if prefix.len() + name.len() + suffix.len() <= NAME_MAX {
+        sibling.push(name);
+    } else {
+        let hash = blake3::hash(name.as_encoded_bytes()).to_hex();
+        let name = name.to_string_lossy();
+        let mut keep = NAME_MAX - prefix.len() - suffix.len() - 17;
+        while !name.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        sibling.push(&name[..keep]);
+        sibling.push("~");
+        sibling.push(&hash[..16]);
+    }
+
+    sibling.push(suffix);
+    target.with_file_name(sibling)
+}
+
+/// Copies the data of `reader` into `writer`, checking for cancellation
+/// between chunks
+///
+/// The file is cloned first when [`OperationOptions::reflink`] allows it.
+/// Otherwise holes are recreated instead of written out, as selected by
+/// [`OperationOptions::sparse`], and the remaining files are copied by the
+/// kernel when it can, in userspace when it cannot.
+///
+/// `resume` holds the journal of a resumable copy and the offset it
+/// continues from; the journal is updated as the copy progresses.
+fn copy_contents(
+    reader: &mut File,
+    writer: &mut File,
+    source: &Path,
+    target: &Path,
+    metadata: &fs::Metadata,
+    resume: Option<(&Journal, u64)>,
+    context: &OperationContext,
+) -> Result<CopyStrategy, XcomError> {
+    let reflink = context.options.reflink;
+    if reflink != ReflinkMode::Never {
+        match reflink::clone_file(reader, writer) {
+            Ok(()) => {
+                context.progress.add_bytes(metadata.len());
+                return Ok(CopyStrategy::Reflink);
+            }
+            Err(e) if reflink == ReflinkMode::Always => {
+                return Err(XcomError::io("clone", target, e));
+            }
+            Err(_) => {}
+        }
+    }
+
+    let start = resume.map_or(0, |(_, start)| start);
+    context.progress.add_bytes(start);
+
+    let mode = context.options.sparse;
+    let segments = match mode {
+        SparseMode::Never => None,
+        _ if sparse::has_holes(metadata) => sparse::data_segments(reader, metadata.len()),
+        _ => None,
+    };
+
+    let mut stream = DataStream {
+        reader,
+        writer,
+        source,
+        target,
+        context,
+        skip_zeros: mode == SparseMode::Always,
+        buffer: vec![0u8; COPY_BUFFER_SIZE],
+        journal: resume.map(|(journal, _)| journal),
+        checkpointed: start,
+    };
+
+    let (end, strategy) = match &segments {
+        Some(segments) => {
+            let mut position = start;
+            for &(offset, length) in segments {
+                let end = offset + length;
+                if end <= start {
+                    continue;
+                }
+                // Holes are never written, but they count as done
+                let offset = offset.max(start);
+                context.progress.add_bytes(offset.saturating_sub(position));
+                stream.copy(offset, Some(end - offset))?;
+                position = end;
+            }
+            context
+                .progress
+                .add_bytes(metadata.len().saturating_sub(position));
+            (metadata.len(), CopyStrategy::Sparse)
+        }
+        None if stream.skip_zeros => (stream.copy(start, None)?, CopyStrategy::Sparse),
+        // copy_file_range may share data as well, so only in auto mode
+        None if reflink == ReflinkMode::Auto => match stream.copy_in_kernel(start)? {
+            Some(end) => (end, CopyStrategy::CopyFileRange),
+            None => (stream.copy(start, None)?, CopyStrategy::Buffered),
+        },
+        None => (stream.copy(start, None)?, CopyStrategy::Buffered),
+    };
+
+    if segments.is_some() || stream.skip_zeros || start > 0 {
+        // A hole at the end of the file only exists once its length is set,
+        // and a resumed target may hold more than what was recorded
+        stream
+            .writer
+            .set_len(end)
+            .map_err(|e| XcomError::io("write", target, e))?;
+    }
+
+    Ok(strategy)
+}
+
+/// Both ends of a file data copy
+struct DataStream<'a> {
+    reader: &'a mut File,
+    writer: &'a mut File,
+    source: &'a Path,
+    target: &'a Path,
+    context: &'a OperationContext<'a>,
+    /// Leave all-zero blocks as holes
+    skip_zeros: bool,
+    buffer: Vec<u8>,
+    /// Journal updated every [`CHECKPOINT_INTERVAL`] bytes
+    journal: Option<&'a Journal>,
+    /// Offset last recorded in the journal
+    checkpointed: u64,
+}
+
+impl DataStream<'_> {
+    /// Copies `length` bytes starting at `offset`, or everything up to the
+    /// end of the file, and returns the offset reached
+    fn copy(&mut self, offset: u64, length: Option<u64>) -> Result<u64, XcomError> {
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| XcomError::io("read", self.source, e))?;
+        self.writer
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| XcomError::io("write", self.target, e))?;
+
+        let mut position = offset;
+        let mut remaining = length.unwrap_or(u64::MAX);
+        while remaining > 0 {
+            if self.context.options.cancel.is_cancelled() {
+                return Err(XcomError::Cancelled);
+            }
+
+            let wanted = remaining.min(self.buffer.len() as u64) as usize;
+            let read = self
+                .reader
+                .read(&mut self.buffer[..wanted])
+                .map_err(|e| XcomError::io("read", self.source, e))?;
+            if read == 0 {
+                break;
+            }
+
+            self.write(read)?;
+            self.context.progress.add_bytes(read as u64);
+            position += read as u64;
+            remaining -= read as u64;
+            self.checkpoint(position)?;
+        }
+
+        Ok(position)
+    }
+
+    /// Copies the rest of the file from `start` with `copy_file_range`
+    ///
+    /// Returns `None`, with nothing copied, when the kernel cannot copy
+    /// between these files; the caller then copies them in userspace.
+    fn copy_in_kernel(&mut self, start: u64) -> Result<Option<u64>, XcomError> {
+        self.reader
+            .seek(SeekFrom::Start(start))
+            .map_err(|e| XcomError::io("read", self.source, e))?;
+        self.writer
+            .seek(SeekFrom::Start(start))
+            .map_err(|e| XcomError::io("write", self.target, e))?;
+
+        let mut position = start;
+        loop {
+            if self.context.options.cancel.is_cancelled() {
+                return Err(XcomError::Cancelled);
+            }
+
+            match reflink::copy_range(self.reader, self.writer, COPY_BUFFER_SIZE) {
+                // Files such as those in /proc have data beyond their size
+                Ok(0) if position == start => return Ok(None),
+                Ok(0) => return Ok(Some(position)),
+                Ok(copied) => {
+                    self.context.progress.add_bytes(copied as u64);
+                    position += copied as u64;
+                    self.checkpoint(position)?;
+                }
+                Err(_) if position == start => return Ok(None),
+                Err(e) => return Err(XcomError::io("write", self.target, e)),
+            }
+        }
+    }
+
+    /// Updates the journal once enough data was copied since the last time
+    fn checkpoint(&mut self, position: u64) -> Result<(), XcomError> {
+        match self.journal {
+            Some(journal) if position >= self.checkpointed + CHECKPOINT_INTERVAL => {
+                journal.checkpoint(self.writer, self.target)?;
+                self.checkpointed = position;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the first `len` bytes of the buffer
+    fn write(&mut self, len: usize) -> Result<(), XcomError> {
+        let data = &self.buffer[..len];
+        if !self.skip_zeros {
+            return self
+                .writer
+                .write_all(data)
+                .map_err(|e| XcomError::io("write", self.target, e));
+        }
+
+        for block in data.chunks(SPARSE_BLOCK_SIZE) {
+            let result = if block.iter().all(|&byte| byte == 0) {
+                self.writer
+                    .seek(SeekFrom::Current(block.len() as i64))
+                    .map(|_| ())
+            } else {
+                self.writer.write_all(block)
+            };
+            result.map_err(|e| XcomError::io("write", self.target, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn copy_dir(
+    source: &Path,
+    target: &Path,
+    metadata: &fs::Metadata,
+    preserve: Preserve,
+    symlinks: SymlinkMode,
+    context: &OperationContext,
+) -> Result<(), XcomError> {
+    fs::create_dir_all(target).map_err(|e| XcomError::io("create directory", target, e))?;
+
+    for entry in read_dir(source)? {
+        if symlinks == SymlinkMode::Follow && is_link_to_parent(&entry, source) {
+            logs(&format!(
+                "WARNING: link \"{}\" points to a parent directory, not followed",
+                entry.display()
+            ));
+            continue;
+        }
+
+        let name = entry
+            .file_name()
+            .map(|n| n.to_os_string())
+            .unwrap_or_default();
+        copy_tree(&entry, &target.join(name), preserve, symlinks, context)?;
+    }
+
+    // Last, as writing the contents changes the directory's times
+    let lost = preserve::apply(source, metadata, target, preserve)?;
+    context.notes.lost_attributes(target, lost);
+    Ok(())
+}
+
+fn ensure_parent(target: &Path) -> Result<(), XcomError> {
+    match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            fs::create_dir_all(parent).map_err(|e| XcomError::io("create directory", parent, e))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Total size of the files and links at or below `path`, links not
+/// followed
+pub(crate) fn tree_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    read_dir(path)
+        .map(|entries| entries.iter().map(|entry| tree_size(entry)).sum())
+        .unwrap_or(0)
+}
+
+fn read_dir(path: &Path) -> Result<Vec<PathBuf>, XcomError> {
+    let entries = fs::read_dir(path)
+        .map_err(|e| XcomError::io("read directory", path, e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+
+    Ok(entries)
+}
+
+/// Returns true if `path` is `dir` itself or lies somewhere below it
+fn is_inside(path: &Path, dir: &Path) -> bool {
+    let dir = match dir.canonicalize() {
+        Ok(dir) => dir,
+        Err(_) => return false,
+    };
+
+    // The target may not exist yet, so resolve the closest existing ancestor
+    let mut current = Some(path);
+    while let Some(candidate) = current {
+        if let Ok(resolved) = candidate.canonicalize() {
+            let rest = path.strip_prefix(candidate).unwrap_or(Path::new(""));
+            return resolved.join(rest).starts_with(&dir);
+        }
+        current = candidate.parent();
+    }
+
+    false
+}
+
+/// Returns true if `link` is a symbolic link to `dir` or one of its parents
+fn is_link_to_parent(link: &Path, dir: &Path) -> bool {
+    let is_link = fs::symlink_metadata(link)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false);
+
+    match (is_link, link.canonicalize(), dir.canonicalize()) {
+        (true, Ok(resolved), Ok(dir)) => dir.starts_with(resolved),
+        _ => false,
+    }
+}
+
+/// Returns true if both paths lead to the same existing file or directory
+pub(crate) fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::CancelAfter;
+    use crate::report::{ItemReport, Outcome};
+    use crate::{CancellationToken, FsyncMode};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    /// Runs `f` with a context for `options`, reporting progress to
+    /// `options.progress`
+    fn with_context<R>(options: &OperationOptions, f: impl FnOnce(&OperationContext) -> R) -> R {
+        let progress = ProgressTracker::new(options.progress.clone(), 0, 0);
+        let notes = ItemNotes::new();
+        let context = OperationContext {
+            options,
+            progress: &progress,
+            notes: &notes,
+        };
+        f(&context)
+    }
+
+    #[test]
+    fn test_copy_files_and_directories() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "a").unwrap();
+        fs::write(src.join("sub").join("b.txt"), "b").unwrap();
+
+        let dest = tmp.path().join("dest");
+        let result = native_operation(std::slice::from_ref(&src), &dest, FileOperation::Copy);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(fs::read_to_string(dest.join("src/a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dest.join("src/sub/b.txt")).unwrap(), "b");
+        assert!(src.join("a.txt").exists());
+    }
+
+    #[test]
+    fn test_move_merges_into_existing_directory() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("data");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("new.txt"), "new").unwrap();
+
+        let dest = tmp.path().join("dest");
+        fs::create_dir_all(dest.join("data")).unwrap();
+        fs::write(dest.join("data/old.txt"), "old").unwrap();
+
+        let result = native_operation(std::slice::from_ref(&src), &dest, FileOperation::Move);
+
+        assert_eq!(result, Ok(true));
+        assert!(!src.exists());
+        assert!(dest.join("data/new.txt").exists());
+        assert!(dest.join("data/old.txt").exists());
+    }
+
+    #[test]
+    fn test_transfer_creates_target_parents() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("a.txt");
+        fs::write(&src, "abc").unwrap();
+
+        let target = tmp.path().join("x/y/renamed.txt");
+        let transfers = [Transfer::new(&src, &target)];
+        let (result, progress) = with_context(&OperationOptions::default(), |context| {
+            let result = native_transfer(&transfers, FileOperation::Copy, context);
+            (result, context.progress.snapshot())
+        });
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(fs::read_to_string(target).unwrap(), "abc");
+        assert_eq!(progress.bytes_done, 3);
+        assert_eq!(progress.files_done, 1);
+    }
+
+    #[test]
+    fn test_parallel_transfer_reports_failures_in_order() {
+        let tmp = tempdir().unwrap();
+        let mut transfers = Vec::new();
+        for i in 0..50 {
+            let src = tmp.path().join(format!("src/{}.txt", i));
+            if i % 10 != 3 {
+                fs::create_dir_all(src.parent().unwrap()).unwrap();
+                fs::write(&src, i.to_string()).unwrap();
+            }
+            transfers.push(Transfer::new(
+                &src,
+                tmp.path().join(format!("dst/{}.txt", i)),
+            ));
+        }
+
+        let options = OperationOptions {
+            jobs: 4,
+            ..Default::default()
+        };
+        let (result, progress) = with_context(&options, |context| {
+            let result = native_transfer(&transfers, FileOperation::Copy, context);
+            (result, context.progress.snapshot())
+        });
+
+        let error = result.unwrap_err();
+        let failed: Vec<_> = error.failures().iter().map(|f| f.source.clone()).collect();
+        let missing: Vec<_> = [3, 13, 23, 33, 43]
+            .iter()
+            .map(|i| tmp.path().join(format!("src/{}.txt", i)))
+            .collect();
+        assert_eq!(failed, missing);
+
+        assert_eq!(progress.files_done, 50);
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("dst/49.txt")).unwrap(),
+            "49"
+        );
+    }
+
+    #[test]
+    fn test_parallel_transfers_to_one_target_run_in_order() {
+        let tmp = tempdir().unwrap();
+        let mut transfers = Vec::new();
+        for (dir, byte) in [("a", 1u8), ("b", 2u8)] {
+            let src = tmp.path().join(dir).join("x.bin");
+            fs::create_dir_all(src.parent().unwrap()).unwrap();
+            fs::write(&src, vec![byte; COPY_BUFFER_SIZE * 8]).unwrap();
+            transfers.push(Transfer::new(&src, tmp.path().join("dst/x.bin")));
+        }
+        let other = tmp.path().join("c.txt");
+        fs::write(&other, "c").unwrap();
+        transfers.push(Transfer::new(&other, tmp.path().join("dst/c.txt")));
+
+        let options = OperationOptions {
+            jobs: 2,
+            reflink: ReflinkMode::Never,
+            ..Default::default()
+        };
+        let result = with_context(&options, |context| {
+            native_transfer(&transfers, FileOperation::Copy, context)
+        });
+
+        assert_eq!(result, Ok(true));
+        let copied = fs::read(tmp.path().join("dst/x.bin")).unwrap();
+        assert!(copied == vec![2u8; COPY_BUFFER_SIZE * 8]);
+        assert!(!partial_path(&tmp.path().join("dst/x.bin")).exists());
+    }
+
+    #[test]
+    fn test_copy_replaces_target_atomically() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("a.txt");
+        let target = tmp.path().join("dst/a.txt");
+        let other = tmp.path().join("dst/other.txt");
+        fs::create_dir_all(tmp.path().join("dst")).unwrap();
+        fs::write(&src, "new").unwrap();
+        fs::write(&other, "old").unwrap();
+        fs::hard_link(&other, &target).unwrap();
+
+        let options = OperationOptions {
+            fsync: FsyncMode::All,
+            ..Default::default()
+        };
+        with_context(&options, |context| copy_item(&src, &target, context)).unwrap();
+
+        // A new file took the name; the old one was never written to
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&other).unwrap(), "old");
+        assert!(!partial_path(&target).exists());
+    }
+
+    #[test]
+    fn test_long_names_get_short_temporary_names() {
+        let tmp = tempdir().unwrap();
+        let name = "x".repeat(250);
+        let src = tmp.path().join(&name);
+        fs::write(&src, "abc").unwrap();
+
+        let target = tmp.path().join("dst").join(&name);
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        with_context(&OperationOptions::default(), |context| {
+            copy_item(&src, &target, context)
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "abc");
+
+        let partial = partial_path(&target);
+        let other = partial_path(&target.with_file_name(format!("{}y", name)));
+        assert!(partial.file_name().unwrap().len() <= NAME_MAX);
+        assert_ne!(partial, other);
+        assert_eq!(partial, partial_path(&target));
+        assert_eq!(
+            partial_path(Path::new("dir/a.txt")),
+            PathBuf::from("dir/.a.txt.xcom-tmp")
+        );
+    }
+
+    #[test]
+    fn test_cancel_removes_partial_file() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("big.bin");
+        fs::write(&src, vec![7u8; COPY_BUFFER_SIZE * 3]).unwrap();
+        let target = tmp.path().join("copy.bin");
+
+        let cancel = CancellationToken::new();
+        let options = OperationOptions {
+            progress: Some(Arc::new(CancelAfter(cancel.clone(), 0))),
+            cancel,
+            ..Default::default()
+        };
+
+        let transfers = [Transfer::new(&src, &target)];
+        let (result, progress) = with_context(&options, |context| {
+            let result = native_transfer(&transfers, FileOperation::Copy, context);
+            (result, context.progress.snapshot())
+        });
+
+        assert_eq!(result, Ok(false));
+        assert!(!target.exists());
+        assert_eq!(progress.bytes_done, COPY_BUFFER_SIZE as u64);
+    }
+
+    #[test]
+    fn test_cancel_keeps_earlier_failures() {
+        let tmp = tempdir().unwrap();
+        let missing = tmp.path().join("missing.bin");
+        let src = tmp.path().join("big.bin");
+        fs::write(&src, vec![7u8; COPY_BUFFER_SIZE * 3]).unwrap();
+
+        let cancel = CancellationToken::new();
+        let options = OperationOptions {
+            progress: Some(Arc::new(CancelAfter(cancel.clone(), 0))),
+            cancel,
+            ..Default::default()
+        };
+        let transfers = [
+            Transfer::new(&missing, tmp.path().join("dst/missing.bin")),
+            Transfer::new(&src, tmp.path().join("dst/big.bin")),
+        ];
+        let result = with_context(&options, |context| {
+            native_transfer(&transfers, FileOperation::Copy, context)
+        });
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, XcomError::Aborted(_)));
+        assert_eq!(error.failures().len(), 1);
+        assert!(error.is_not_found());
+    }
+
+    #[test]
+    fn test_resume_continues_interrupted_copy() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("big.bin");
+        let len = CHECKPOINT_INTERVAL as usize + 3 * COPY_BUFFER_SIZE;
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        fs::write(&src, &data).unwrap();
+        let target = tmp.path().join("copy.bin");
+        let transfers = [Transfer::new(&src, &target)];
+
+        let copy = |cancel_after: u64| {
+            let cancel = CancellationToken::new();
+            let options = OperationOptions {
+                resume: true,
+                progress: Some(Arc::new(CancelAfter(cancel.clone(), cancel_after))),
+                cancel,
+                ..Default::default()
+            };
+            with_context(&options, |context| {
+                let result = native_transfer(&transfers, FileOperation::Copy, context);
+                (result, context.progress.snapshot().bytes_done)
+            })
+        };
+
+        let (result, _) = copy(CHECKPOINT_INTERVAL);
+        assert_eq!(result, Ok(false));
+        assert!(!target.exists());
+        assert!(partial_path(&target).exists());
+        let journal = fs::read_to_string(resume::journal_path(&target)).unwrap();
+        assert!(!journal.ends_with("offset=0\n"));
+
+        let (result, bytes_done) = copy(u64::MAX);
+        assert_eq!(result, Ok(true));
+        assert_eq!(bytes_done, len as u64);
+        assert!(!resume::has_journal(&target));
+        assert!(fs::read(&target).unwrap() == data);
+    }
+
+    #[test]
+    fn test_copy_without_resume_removes_stale_journal() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("a.txt");
+        fs::write(&src, "new").unwrap();
+        let target = tmp.path().join("copy.txt");
+        fs::write(resume::journal_path(&target), "stale").unwrap();
+
+        with_context(&OperationOptions::default(), |context| {
+            copy_item(&src, &target, context)
+        })
+        .unwrap();
+
+        assert!(!resume::has_journal(&target));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_move_across_devices_keeps_nothing_behind() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("tree");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.txt"), "abc").unwrap();
+        let target = tmp.path().join("moved");
+
+        let metadata = fs::symlink_metadata(&src).unwrap();
+        with_context(&OperationOptions::default(), |context| {
+            move_across_devices(&src, &target, &metadata, context)
+        })
+        .unwrap();
+
+        assert!(!src.exists());
+        assert_eq!(fs::read_to_string(target.join("sub/a.txt")).unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_moved_bytes_are_counted_once() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("tree");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.txt"), "abc").unwrap();
+        fs::write(src.join("b.txt"), "de").unwrap();
+
+        let options = OperationOptions::default();
+        let renamed = tmp.path().join("renamed");
+        let bytes_done = with_context(&options, |context| {
+            move_item(&src, &renamed, context).unwrap();
+            context.progress.snapshot().bytes_done
+        });
+        assert_eq!(bytes_done, 5);
+
+        let copied = tmp.path().join("copied");
+        let metadata = fs::symlink_metadata(&renamed).unwrap();
+        let bytes_done = with_context(&options, |context| {
+            move_across_devices(&renamed, &copied, &metadata, context).unwrap();
+            context.progress.snapshot().bytes_done
+        });
+        assert_eq!(bytes_done, 5);
+    }
+
+    #[test]
+    fn test_failed_cross_device_copy_keeps_source() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("a.txt");
+        fs::write(&src, "abc").unwrap();
+        // A directory in the way makes the copy fail
+        let target = tmp.path().join("blocked");
+        fs::create_dir_all(&target).unwrap();
+
+        let metadata = fs::symlink_metadata(&src).unwrap();
+        let result = with_context(&OperationOptions::default(), |context| {
+            move_across_devices(&src, &target, &metadata, context)
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&src).unwrap(), "abc");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links_are_copied_as_links() {
+        use std::os::unix::fs::symlink;
+
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a.txt"), "a").unwrap();
+        symlink("a.txt", src.join("link")).unwrap();
+        symlink("..", src.join("up")).unwrap();
+
+        let dest = tmp.path().join("dest");
+        let result = native_operation(std::slice::from_ref(&src), &dest, FileOperation::Copy);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            fs::read_link(dest.join("src/link")).unwrap(),
+            PathBuf::from("a.txt")
+        );
+        assert_eq!(
+            fs::read_link(dest.join("src/up")).unwrap(),
+            PathBuf::from("..")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_does_not_loop() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("..", src.join("sub/up")).unwrap();
+
+        let options = OperationOptions {
+            symlinks: SymlinkMode::Follow,
+            ..Default::default()
+        };
+
+        let target = tmp.path().join("dest");
+        with_context(&options, |context| copy_item(&src, &target, context)).unwrap();
+
+        assert!(target.join("sub/a.txt").is_file());
+        assert!(!target.join("sub/up").exists());
+    }
+
+    #[test]
+    fn test_reflink_modes() {
+        let tmp = tempdir().unwrap();
+        let source = tmp.path().join("data.bin");
+        fs::write(&source, vec![7u8; 3 * COPY_BUFFER_SIZE + 5]).unwrap();
+
+        let copy_with = |reflink: ReflinkMode, name: &str| {
+            let options = OperationOptions {
+                reflink,
+                ..Default::default()
+            };
+            let target = tmp.path().join(name);
+            let mut item = ItemReport {
+                source: source.clone(),
+                destination: target.clone(),
+                outcome: Outcome::Copied,
+                replaced: false,
+                bytes: 0,
+                lost_attributes: Vec::new(),
+                linked_to: None,
+                strategy: None,
+            };
+            let result = with_context(&options, |context| {
+                let result = copy_item(&source, &target, context);
+                context.notes.fill(&mut item);
+                result
+            });
+            (result, target, item.strategy)
+        };
+
+        let (result, target, strategy) = copy_with(ReflinkMode::Never, "never");
+        result.unwrap();
+        assert_eq!(strategy, Some(CopyStrategy::Buffered));
+        assert_eq!(fs::read(&source).unwrap(), fs::read(target).unwrap());
+
+        let (result, target, strategy) = copy_with(ReflinkMode::Auto, "auto");
+        result.unwrap();
+        assert!(strategy.is_some());
+        assert_eq!(fs::read(&source).unwrap(), fs::read(target).unwrap());
+
+        // Depends on the filesystem of the temporary directory
+        let (result, target, strategy) = copy_with(ReflinkMode::Always, "always");
+        match result {
+            Ok(()) => assert_eq!(strategy, Some(CopyStrategy::Reflink)),
+            Err(_) => assert!(!target.exists()),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sparse_modes() {
+        use std::os::unix::fs::MetadataExt;
+
+        fn copy_with(sparse: SparseMode, source: &Path, target: &Path) -> fs::Metadata {
+            // A clone would keep the holes whatever the mode
+            let options = OperationOptions {
+                sparse,
+                reflink: ReflinkMode::Never,
+                ..Default::default()
+            };
+            with_context(&options, |context| copy_item(source, target, context)).unwrap();
+            assert_eq!(fs::read(source).unwrap(), fs::read(target).unwrap());
+            fs::metadata(target).unwrap()
+        }
+
+        let tmp = tempdir().unwrap();
+        let holes = tmp.path().join("holes.img");
+        let mut file = File::create(&holes).unwrap();
+        file.seek(SeekFrom::Start(4 << 20)).unwrap();
+        file.write_all(b"data").unwrap();
+        file.set_len(8 << 20).unwrap();
+        drop(file);
+
+        let zeros = tmp.path().join("zeros.img");
+        let mut data = vec![0u8; 1 << 20];
+        data.push(1);
+        fs::write(&zeros, data).unwrap();
+
+        if !sparse::has_holes(&fs::metadata(&holes).unwrap()) {
+            // The temporary directory does not support sparse files
+            return;
+        }
+
+        let auto = copy_with(SparseMode::Auto, &holes, &tmp.path().join("auto"));
+        assert!(auto.blocks() * 512 < auto.len());
+
+        let never = copy_with(SparseMode::Never, &holes, &tmp.path().join("never"));
+        assert!(never.blocks() * 512 >= never.len());
+
+        let always = copy_with(SparseMode::Always, &zeros, &tmp.path().join("always"));
+        assert!(always.blocks() * 512 < always.len());
+    }
+
+    #[test]
+    fn test_missing_source_is_an_error() {
+        let tmp = tempdir().unwrap();
+        let missing = tmp.path().join("missing.txt");
+
+        let result = native_operation(&[missing], tmp.path(), FileOperation::Copy);
+        let error = result.unwrap_err();
+        assert_eq!(error.failures().len(), 1);
+        assert!(error.is_not_found());
+    }
+
+    #[test]
+    fn test_copy_into_itself_is_rejected() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+
+        let result = native_operation(std::slice::from_ref(&src), &src, FileOperation::Copy);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,111 @@
+// File: src\options.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Options shared by process_sources and perform_operation
+// License: MIT
+
+//! Operation options
+
+use crate::cancel::CancellationToken;
+use crate::conflict::ConflictPolicy;
+use crate::filter::{Filter, FilterRules};
+use crate::fsync::FsyncMode;
+use crate::preserve::Preserve;
+use crate::progress::ProgressObserver;
+use crate::reflink::ReflinkMode;
+use crate::sparse::SparseMode;
+use crate::symlink::SymlinkMode;
+use crate::verify::HashAlgorithm;
+use std::fmt;
+use std::sync::Arc;
+
+/// Settings controlling how an operation is carried out
+#[derive(Clone)]
+pub struct OperationOptions {
+    /// What to do when a destination file already exists
+    pub conflict: ConflictPolicy,
+    /// Plan the operation and report it without touching the filesystem
+    pub dry_run: bool,
+    /// Attributes carried over to copied files and directories
+    pub preserve: Preserve,
+    /// How symbolic links among the sources are handled
+    pub symlinks: SymlinkMode,
+    /// Recreate hard links between copied files instead of duplicating
+    /// their data (Unix only)
+    pub hard_links: bool,
+    /// How holes in sparse files are handled by the native engine
+    pub sparse: SparseMode,
+    /// Whether the native engine clones files on copy-on-write filesystems
+    pub reflink: ReflinkMode,
+    /// Number of files the native engine transfers at once; 0 starts one
+    /// worker per CPU
+    pub jobs: usize,
+    /// Hash every copied file and its source and fail the copies that
+    /// differ; moves across devices keep the source of those
+    pub verify: Option<HashAlgorithm>,
+    /// Keep interrupted copies of large files with a journal, and continue
+    /// those left by an earlier run
+    pub resume: bool,
+    /// What is flushed to the device as files are written
+    pub fsync: FsyncMode,
+    /// Include and exclude rules selecting what is transferred, both in
+    /// directories and in the matches of wildcard patterns
+    pub filter: FilterRules,
+    /// Leave out what the ignore files of git repositories among the
+    /// sources ignore: `.gitignore`, `.ignore` and `.git/info/exclude`
+    pub respect_gitignore: bool,
+    /// Only transfer the files, links and other non-directories this
+    /// filter matches
+    pub select: Option<Filter>,
+    /// Receives progress updates while files are transferred
+    pub progress: Option<Arc<dyn ProgressObserver>>,
+    /// Stops the operation when cancelled
+    pub cancel: CancellationToken,
+}
+
+impl Default for OperationOptions {
+    /// Transfers one file at a time
+    fn default() -> Self {
+        OperationOptions {
+            conflict: ConflictPolicy::default(),
+            dry_run: false,
+            preserve: Preserve::default(),
+            symlinks: SymlinkMode::default(),
+            hard_links: false,
+            sparse: SparseMode::default(),
+            reflink: ReflinkMode::default(),
+            jobs: 1,
+            verify: None,
+            resume: false,
+            fsync: FsyncMode::default(),
+            filter: FilterRules::default(),
+            respect_gitignore: false,
+            select: None,
+            progress: None,
+            cancel: CancellationToken::default(),
+        }
+    }
+}
+
+impl fmt::Debug for OperationOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OperationOptions")
+            .field("conflict", &self.conflict)
+            .field("dry_run", &self.dry_run)
+            .field("preserve", &self.preserve)
+            .field("symlinks", &self.symlinks)
+            .field("hard_links", &self.hard_links)
+            .field("sparse", &self.sparse)
+            .field("reflink", &self.reflink)
+            .field("jobs", &self.jobs)
+            .field("verify", &self.verify)
+            .field("resume", &self.resume)
+            .field("fsync", &self.fsync)
+            .field("filter", &self.filter)
+            .field("respect_gitignore", &self.respect_gitignore)
+            .field("select", &self.select)
+            .field("progress", &self.progress.is_some())
+            .field("cancelled", &self.cancel.is_cancelled())
+            .finish()
+    }
+}
@@ -0,0 +1,159 @@
+// File: src\parallel.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Worker threads for the native engine
+// License: MIT
+
+//! Parallel execution
+//!
+//! [`run_ordered`] hands the items of an operation to a fixed number of
+//! worker threads. Each worker takes the next item only when it is done
+//! with the previous one, so memory use depends on the number of workers,
+//! not on the number of items. Log entries are held back and written in
+//! item order, as a sequential run would have written them.
+
+use crate::{capture_logs, write_logs};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Number of workers used when one per CPU is asked for
+pub(crate) fn available_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Runs `task` on every item, `jobs` items at a time
+///
+/// No new item is started once `stop` returns true.
+///
+/// # Returns
+///
+/// The result of every item, in item order; `None` for the items that
+/// were not started.
+pub(crate) fn run_ordered<I, R, S, T>(items: &[I], jobs: usize, stop: S, task: T) -> Vec<Option<R>>
+where
+    I: Sync,
+    R: Send,
+    S: Fn() -> bool + Sync,
+    T: Fn(&I) -> R + Sync,
+{
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+
+    if jobs <= 1 || items.len() <= 1 {
+        for (item, result) in items.iter().zip(results.iter_mut()) {
+            if stop() {
+                break;
+            }
+            *result = Some(task(item));
+        }
+        return results;
+    }
+
+    let next = AtomicUsize::new(0);
+    let log = OrderedLog::default();
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    while !stop() {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+
+                        let (result, entries) = capture_logs(|| task(item));
+                        log.done(index, entries);
+                        done.push((index, result));
+                    }
+                    done
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let done = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (index, result) in done {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    // Entries that came after an item never started
+    log.flush_all();
+    results
+}
+
+/// Writes the log entries of each item once those of every earlier item
+/// are written
+#[derive(Default)]
+struct OrderedLog {
+    state: Mutex<OrderedLogState>,
+}
+
+#[derive(Default)]
+struct OrderedLogState {
+    /// Index of the first item whose entries are not written yet
+    next: usize,
+    /// Entries of finished items waiting for an earlier one
+    pending: BTreeMap<usize, Vec<String>>,
+}
+
+impl OrderedLog {
+    fn done(&self, index: usize, entries: Vec<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.insert(index, entries);
+
+        loop {
+            let next = state.next;
+            match state.pending.remove(&next) {
+                Some(entries) => {
+                    write_logs(&entries);
+                    state.next += 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn flush_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        for entries in std::mem::take(&mut state.pending).into_values() {
+            write_logs(&entries);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_results_in_item_order() {
+        let items: Vec<u64> = (0..100).collect();
+        let results = run_ordered(&items, 4, || false, |n| n * 2);
+
+        let expected: Vec<Option<u64>> = items.iter().map(|n| Some(n * 2)).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_stop_leaves_items_unstarted() {
+        let items: Vec<u64> = (0..100).collect();
+        let started = AtomicUsize::new(0);
+        let results = run_ordered(
+            &items,
+            4,
+            || started.load(Ordering::SeqCst) >= 10,
+            |_| started.fetch_add(1, Ordering::SeqCst),
+        );
+
+        let finished = results.iter().filter(|result| result.is_some()).count();
+        assert!((10..14).contains(&finished));
+        assert!(results[99].is_none());
+    }
+}
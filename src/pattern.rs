@@ -0,0 +1,291 @@
+// File: src\pattern.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Wildcard expansion of source arguments
+// License: MIT
+
+//! Source patterns
+//!
+//! Shells on Windows leave wildcards to the program, so
+//! [`process_sources`](crate::process_sources) expands them itself: `*` and
+//! `?` within a name, character classes such as `[ab]` or `[!0-9]`, and
+//! `**` for any number of directories. Brace alternatives such as
+//! `{src,tests}/*.rs` are expanded first, like a shell does, and sources
+//! starting with `!` leave out the paths they match.
+
+use crate::error::XcomError;
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Whether `source` is a pattern rather than a plain path
+pub(crate) fn is_pattern(source: &str) -> bool {
+    source.contains(['*', '?', '['])
+}
+
+/// Expands the brace alternatives of `source`, e.g. `{a,b}.txt` into
+/// `a.txt` and `b.txt`, nested ones included
+///
+/// Braces without a comma, such as `{a}`, are kept as they are.
+pub(crate) fn expand_braces(source: &str) -> Vec<String> {
+    let Some((open, close, alternatives)) = find_braces(source) else {
+        return vec![source.to_string()];
+    };
+
+    let (prefix, suffix) = (&source[..open], &source[close + 1..]);
+    alternatives
+        .iter()
+        .flat_map(|alternative| expand_braces(&format!("{}{}{}", prefix, alternative, suffix)))
+        .collect()
+}
+
+/// Finds the first `{...}` group with alternatives, returning the offsets
+/// of its braces and the alternatives
+fn find_braces(source: &str) -> Option<(usize, usize, Vec<&str>)> {
+    for (open, _) in source.match_indices('{') {
+        let mut depth = 0;
+        let mut start = open + 1;
+        let mut alternatives = Vec::new();
+
+        for (offset, c) in source[open..].char_indices() {
+            let at = open + offset;
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        if alternatives.is_empty() {
+                            break;
+                        }
+                        alternatives.push(&source[start..at]);
+                        return Some((open, at, alternatives));
+                    }
+                }
+                ',' if depth == 1 => {
+                    alternatives.push(&source[start..at]);
+                    start = at + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Paths left out by the `!` patterns among the sources
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Exclusions {
+    globs: GlobSet,
+}
+
+impl Exclusions {
+    /// Builds the exclusions from patterns given without their `!`
+    pub(crate) fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<Self, XcomError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            for alternative in expand_braces(pattern) {
+                let glob = build_glob(&alternative)
+                    .map_err(|e| XcomError::invalid(Path::new(pattern), e.to_string()))?;
+                builder.add(glob);
+            }
+        }
+
+        let globs = builder
+            .build()
+            .map_err(|e| XcomError::invalid(Path::new(""), e.to_string()))?;
+        Ok(Exclusions { globs })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    /// Whether `path`, as found among or below the sources, is left out
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        if self.globs.is_empty() {
+            return false;
+        }
+        let path = path.strip_prefix(".").unwrap_or(path);
+        self.globs.is_match(path)
+    }
+}
+
+/// Lists the paths matching `pattern`, sorted by name
+///
+/// A directory that matches is listed without its contents. Paths for
+/// which `skip` returns true are not searched; it is given the path, the
+/// path relative to where matching starts and whether it is a directory.
+/// A pattern matching nothing is returned as is, to be reported as a
+/// missing path.
+pub(crate) fn expand<S>(pattern: &str, skip: S) -> Result<Vec<PathBuf>, XcomError>
+where
+    S: Fn(&Path, &Path, bool) -> bool,
+{
+    let (base, glob) = split_pattern(pattern);
+    let matcher =
+        compile(&glob).map_err(|e| XcomError::invalid(Path::new(pattern), e.to_string()))?;
+    let depth = if glob.contains("**") {
+        usize::MAX
+    } else {
+        glob.split('/').count()
+    };
+
+    let root = if base.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        &base
+    };
+
+    let mut matches = Vec::new();
+    let mut walk = WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(depth)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walk.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let relative = match entry.path().strip_prefix(root) {
+            Ok(relative) => relative,
+            Err(_) => continue,
+        };
+
+        if skip(entry.path(), relative, entry.file_type().is_dir()) {
+            if entry.file_type().is_dir() {
+                walk.skip_current_dir();
+            }
+            continue;
+        }
+
+        if matcher.is_match(relative) {
+            matches.push(base.join(relative));
+            if entry.file_type().is_dir() {
+                // Its contents come along with it
+                walk.skip_current_dir();
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        matches.push(PathBuf::from(pattern));
+    }
+    Ok(matches)
+}
+
+/// Splits `pattern` into the directory where matching starts and the
+/// glob matched below it, with `/` separators
+fn split_pattern(pattern: &str) -> (PathBuf, String) {
+    let mut base = PathBuf::new();
+    let mut glob = Vec::new();
+
+    for component in Path::new(pattern).components() {
+        let name = component.as_os_str().to_string_lossy();
+        if glob.is_empty() && !(matches!(component, Component::Normal(_)) && is_pattern(&name)) {
+            base.push(component);
+        } else {
+            glob.push(name.into_owned());
+        }
+    }
+
+    (base, glob.join("/"))
+}
+
+fn compile(glob: &str) -> Result<GlobMatcher, globset::Error> {
+    Ok(build_glob(glob)?.compile_matcher())
+}
+
+pub(crate) fn build_glob(glob: &str) -> Result<Glob, globset::Error> {
+    GlobBuilder::new(glob)
+        .literal_separator(true)
+        .backslash_escape(cfg!(not(windows)))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_split_pattern() {
+        assert_eq!(
+            split_pattern("data/raw/*.csv"),
+            (PathBuf::from("data/raw"), "*.csv".to_string())
+        );
+        assert_eq!(
+            split_pattern("src/**/mod.rs"),
+            (PathBuf::from("src"), "**/mod.rs".to_string())
+        );
+        assert_eq!(
+            split_pattern("[ab]*"),
+            (PathBuf::new(), "[ab]*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(
+            expand_braces("{src,tests}/**/*.rs"),
+            ["src/**/*.rs", "tests/**/*.rs"]
+        );
+        assert_eq!(expand_braces("a{b,c{d,e}}f"), ["abf", "acdf", "acef"]);
+        assert_eq!(expand_braces("{a}{"), ["{a}{"]);
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let exclusions = Exclusions::new(["**/*.tmp", "logs/{keep,old}.log"]).unwrap();
+        assert!(exclusions.matches(Path::new("a.tmp")));
+        assert!(exclusions.matches(Path::new("./src/deep/a.tmp")));
+        assert!(exclusions.matches(Path::new("logs/old.log")));
+        assert!(!exclusions.matches(Path::new("logs/new.log")));
+        assert!(!Exclusions::default().matches(Path::new("a.tmp")));
+    }
+
+    #[test]
+    fn test_expand_patterns() {
+        let tmp = tempdir().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("data/deep/er")).unwrap();
+        for name in ["a.txt", "b.txt", "b.log", "data/01.csv", "data/123.csv"] {
+            fs::write(root.join(name), "x").unwrap();
+        }
+        fs::write(root.join("data/deep/er/02.csv"), "x").unwrap();
+
+        let expand_in = |pattern: &str| {
+            let pattern = format!("{}/{}", root.display(), pattern);
+            expand(&pattern, |_, _, _| false)
+                .unwrap()
+                .into_iter()
+                .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(expand_in("*.txt"), ["a.txt", "b.txt"].map(PathBuf::from));
+        assert_eq!(expand_in("data/??.csv"), [PathBuf::from("data/01.csv")]);
+        assert_eq!(
+            expand_in("[!a]*"),
+            ["b.log", "b.txt", "data"].map(PathBuf::from)
+        );
+        assert_eq!(
+            expand_in("data/**/*.csv"),
+            ["data/01.csv", "data/123.csv", "data/deep/er/02.csv"].map(PathBuf::from)
+        );
+
+        let pattern = format!("{}/data/**/*.csv", root.display());
+        let skip_deep = |_: &Path, relative: &Path, _| relative.ends_with("deep");
+        assert_eq!(expand(&pattern, skip_deep).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_unmatched_pattern_is_kept() {
+        let tmp = tempdir().unwrap();
+        let pattern = format!("{}/*.none", tmp.path().display());
+        let keep = |_: &Path, _: &Path, _| false;
+        assert_eq!(expand(&pattern, keep).unwrap(), [PathBuf::from(&pattern)]);
+        assert!(expand("[", keep).is_err());
+    }
+}
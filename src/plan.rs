@@ -0,0 +1,394 @@
+// File: src\plan.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Expansion of sources into per-file transfers
+// License: MIT
+
+//! Operation planning
+//!
+//! Expands the sources of an operation into the directories to create and
+//! the individual files to transfer, so that decisions such as conflict
+//! resolution are taken per file. A directory moved to a new place with
+//! nothing to decide per file is kept whole, to be renamed at once.
+
+use crate::backend::Transfer;
+use crate::filter::{EntryType, Filter, FilterRules};
+use crate::gitignore::IgnoreFiles;
+use crate::pattern::Exclusions;
+use crate::symlink::SymlinkMode;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Directories and files making up one operation
+#[derive(Debug, Default)]
+pub(crate) struct Plan {
+    /// Source directories and their destination, parents first
+    pub dirs: Vec<Transfer>,
+    /// Files (and links copied as links) to transfer, in walk order, and
+    /// directories moved whole
+    pub files: Vec<Transfer>,
+    /// Links left out by the symlink mode, or that cannot be followed
+    pub skipped: Vec<Transfer>,
+    /// Symbolic links whose target does not exist
+    pub dangling: Vec<PathBuf>,
+    /// Links not followed because they point back to one of their parents
+    pub loops: Vec<PathBuf>,
+    pub symlinks: SymlinkMode,
+    /// Sources, and entries below them, left out of the operation
+    pub excluded: Exclusions,
+    /// Destination directory, which filter rules are relative to
+    pub root: PathBuf,
+    pub filter: FilterRules,
+    /// Ignore files of git repositories, when they are respected
+    pub ignored: Option<IgnoreFiles>,
+    /// Metadata filter the files to transfer must match
+    pub select: Option<Filter>,
+    /// Whether directories may be moved whole, by a single rename
+    pub moves_dirs: bool,
+}
+
+impl Plan {
+    pub fn new(symlinks: SymlinkMode) -> Self {
+        Plan {
+            symlinks,
+            ..Default::default()
+        }
+    }
+
+    /// Adds `transfer`, expanding directory sources into their contents
+    pub fn add(&mut self, transfer: Transfer) {
+        if self.leaves_out(&transfer, transfer.source.is_dir()) || self.add_link(&transfer) {
+            return;
+        }
+
+        if !transfer.source.is_dir() {
+            self.files.push(transfer);
+            return;
+        }
+
+        if self.moves_whole(&transfer) {
+            self.files.push(transfer);
+            return;
+        }
+
+        // Directories moved whole into this one now need per-file decisions
+        while let Some(position) = self.files.iter().position(|planned| {
+            overlaps(&planned.target, &transfer.target) && is_dir(&planned.source)
+        }) {
+            let planned = self.files.remove(position);
+            self.expand(planned);
+        }
+
+        self.expand(transfer);
+    }
+
+    /// Plans the directory of `transfer` and everything below it
+    fn expand(&mut self, transfer: Transfer) {
+        let (source, target) = (transfer.source.clone(), transfer.target.clone());
+        self.dirs.push(transfer);
+        self.add_contents(&source, &target);
+    }
+
+    /// Whether the directory of `transfer` can be moved whole: its target
+    /// is free, and nothing below it is left out or may conflict
+    fn moves_whole(&self, transfer: &Transfer) -> bool {
+        self.moves_dirs
+            && self.symlinks == SymlinkMode::Copy
+            && self.excluded.is_empty()
+            && self.filter.is_empty()
+            && self.ignored.is_none()
+            && self.select.is_none()
+            && fs::symlink_metadata(&transfer.target).is_err()
+            && !self
+                .files
+                .iter()
+                .chain(&self.dirs)
+                .any(|planned| overlaps(&planned.target, &transfer.target))
+    }
+
+    /// Adds everything below `source`, replicated under `target`
+    pub fn add_contents(&mut self, source: &Path, target: &Path) {
+        let follow = self.symlinks == SymlinkMode::Follow;
+
+        let mut walk = WalkDir::new(source)
+            .min_depth(1)
+            .follow_links(follow)
+            .into_iter();
+        while let Some(entry) = walk.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    // Links that cannot be followed: loops and dangling links
+                    let transfer = error
+                        .path()
+                        .and_then(|path| relative_transfer(path, source, target))
+                        .filter(|transfer| !self.leaves_out(transfer, false));
+                    if let Some(transfer) = transfer {
+                        if error.loop_ancestor().is_some() {
+                            self.loops.push(transfer.source.clone());
+                            self.skipped.push(transfer);
+                        } else {
+                            self.add_link(&transfer);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let transfer = match relative_transfer(entry.path(), source, target) {
+                Some(transfer) => transfer,
+                None => continue,
+            };
+
+            let is_dir = entry.file_type().is_dir();
+            if self.leaves_out(&transfer, is_dir) {
+                if is_dir {
+                    walk.skip_current_dir();
+                }
+                continue;
+            }
+
+            if entry.path_is_symlink() && self.add_link(&transfer) {
+                continue;
+            }
+
+            if is_dir {
+                self.dirs.push(transfer);
+            } else if entry.file_type().is_file() {
+                self.files.push(transfer);
+            }
+        }
+    }
+
+    /// Drops the directories a metadata filter leaves empty
+    ///
+    /// Only the directories holding files to transfer are kept, and those
+    /// selected themselves when the filter asks for directories.
+    pub fn prune_dirs(&mut self) {
+        let Some(select) = &self.select else {
+            return;
+        };
+
+        let selects_dirs = select.names(EntryType::Dir);
+        let selected_dirs = self.dirs.iter().filter(|dir| {
+            selects_dirs && fs::metadata(&dir.source).is_ok_and(|m| select.matches(&m))
+        });
+        let parents = self.files.iter().filter_map(|file| file.target.parent());
+
+        let mut needed = HashSet::new();
+        for dir in selected_dirs.map(|dir| dir.target.as_path()).chain(parents) {
+            for ancestor in dir.ancestors() {
+                if !needed.insert(ancestor.to_path_buf()) {
+                    break;
+                }
+            }
+        }
+        self.dirs.retain(|dir| needed.contains(&dir.target));
+    }
+
+    /// Whether `source`, found at `relative` below where the search
+    /// started, is left out by exclusions, filter rules, ignore files or
+    /// the metadata filter
+    pub fn skips(&self, source: &Path, relative: &Path, is_dir: bool) -> bool {
+        self.excluded.matches(source)
+            || self.filter.excludes(relative, is_dir)
+            || self
+                .ignored
+                .as_ref()
+                .is_some_and(|ignored| ignored.is_ignored(source, is_dir))
+            || !self.selects(source, is_dir)
+    }
+
+    /// Whether the metadata filter takes `source` along; directories are
+    /// always walked
+    fn selects(&self, source: &Path, is_dir: bool) -> bool {
+        let select = match &self.select {
+            Some(select) if !is_dir => select,
+            _ => return true,
+        };
+
+        let metadata = match self.symlinks {
+            SymlinkMode::Follow => fs::metadata(source).or_else(|_| fs::symlink_metadata(source)),
+            _ => fs::symlink_metadata(source),
+        };
+        metadata.is_ok_and(|metadata| select.matches(&metadata))
+    }
+
+    /// Whether `transfer` is left out, with filter rules matched against
+    /// its destination
+    fn leaves_out(&self, transfer: &Transfer, is_dir: bool) -> bool {
+        let relative = transfer
+            .target
+            .strip_prefix(&self.root)
+            .unwrap_or(&transfer.target);
+        self.skips(&transfer.source, relative, is_dir)
+    }
+
+    /// Plans `transfer` if its source is a symbolic link
+    ///
+    /// Returns false when the link is to be followed, so the caller treats
+    /// the source like whatever it points to.
+    fn add_link(&mut self, transfer: &Transfer) -> bool {
+        let is_link = fs::symlink_metadata(&transfer.source)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if !is_link {
+            return false;
+        }
+
+        let dangling = fs::metadata(&transfer.source).is_err();
+        if dangling {
+            self.dangling.push(transfer.source.clone());
+        }
+
+        match self.symlinks {
+            SymlinkMode::Copy => self.files.push(transfer.clone()),
+            SymlinkMode::Follow if !dangling => return false,
+            SymlinkMode::Follow | SymlinkMode::Skip => self.skipped.push(transfer.clone()),
+        }
+        true
+    }
+}
+
+/// Whether one of the paths is inside the other, or both are the same
+fn overlaps(a: &Path, b: &Path) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// Whether `path` is a directory, not a link to one
+fn is_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+/// Recreates the path of `path` relative to `source` under `target`
+fn relative_transfer(path: &Path, source: &Path, target: &Path) -> Option<Transfer> {
+    let relative = path.strip_prefix(source).ok()?;
+    Some(Transfer::new(path, target.join(relative)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_directories_are_expanded() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.txt"), "a").unwrap();
+
+        let mut plan = Plan::default();
+        plan.add(Transfer::new(&src, "dest/src"));
+        plan.add(Transfer::new("file.txt", "dest/file.txt"));
+
+        let dirs: Vec<PathBuf> = plan.dirs.iter().map(|t| t.target.clone()).collect();
+        assert_eq!(dirs, vec![PathBuf::from("dest/src"), PathBuf::from("dest/src/sub")]);
+        assert_eq!(plan.files.len(), 2);
+        assert_eq!(plan.files[0].target, PathBuf::from("dest/src/sub/a.txt"));
+    }
+
+    #[test]
+    fn test_moved_directories_stay_whole_without_conflicts() {
+        let tmp = tempdir().unwrap();
+        for dir in ["a/x", "b/x"] {
+            fs::create_dir_all(tmp.path().join(dir).join("sub")).unwrap();
+            fs::write(tmp.path().join(dir).join("sub/f.txt"), dir).unwrap();
+        }
+        let dest = tmp.path().join("dest");
+
+        let mut plan = Plan::new(SymlinkMode::Copy);
+        plan.moves_dirs = true;
+        plan.add(Transfer::new(tmp.path().join("a"), dest.join("a")));
+        assert!(plan.dirs.is_empty());
+        assert_eq!(
+            plan.files,
+            vec![Transfer::new(tmp.path().join("a"), dest.join("a"))]
+        );
+
+        // Both directories going to the same place are merged file by file
+        plan.add(Transfer::new(tmp.path().join("a/x"), dest.join("x")));
+        plan.add(Transfer::new(tmp.path().join("b/x"), dest.join("x")));
+        let targets: Vec<&Path> = plan.files.iter().map(|t| t.target.as_path()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                dest.join("a"),
+                dest.join("x/sub/f.txt"),
+                dest.join("x/sub/f.txt")
+            ]
+        );
+
+        // An existing target is merged into file by file too
+        fs::create_dir_all(dest.join("y")).unwrap();
+        plan.add(Transfer::new(tmp.path().join("b"), dest.join("y")));
+        assert_eq!(
+            plan.files.last().unwrap().target,
+            dest.join("y/x/sub/f.txt")
+        );
+    }
+
+    #[test]
+    fn test_selection_prunes_empty_directories() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        for dir in ["big/deep", "small", "empty"] {
+            fs::create_dir_all(src.join(dir)).unwrap();
+        }
+        fs::write(src.join("big/deep/a.bin"), vec![0u8; 2048]).unwrap();
+        fs::write(src.join("small/b.bin"), "b").unwrap();
+
+        let targets = |select: Filter| {
+            let mut plan = Plan {
+                select: Some(select),
+                ..Default::default()
+            };
+            plan.add(Transfer::new(&src, "dest"));
+            plan.prune_dirs();
+            plan.dirs.into_iter().map(|t| t.target).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            targets(Filter::MinSize(1024)),
+            vec![
+                PathBuf::from("dest"),
+                PathBuf::from("dest/big"),
+                PathBuf::from("dest/big/deep")
+            ]
+        );
+        assert_eq!(targets(Filter::Type(EntryType::Dir)).len(), 5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_modes() {
+        use std::os::unix::fs::symlink;
+
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.txt"), "a").unwrap();
+        symlink("sub/a.txt", src.join("link.txt")).unwrap();
+        symlink("missing", src.join("dangling")).unwrap();
+        symlink("..", src.join("sub/parent")).unwrap();
+
+        let mut copy = Plan::new(SymlinkMode::Copy);
+        copy.add_contents(&src, Path::new("dest"));
+        assert_eq!(copy.files.len(), 4);
+        assert_eq!(copy.dangling, vec![src.join("dangling")]);
+
+        let mut skip = Plan::new(SymlinkMode::Skip);
+        skip.add_contents(&src, Path::new("dest"));
+        assert_eq!(skip.files.len(), 1);
+        assert_eq!(skip.skipped.len(), 3);
+
+        let mut follow = Plan::new(SymlinkMode::Follow);
+        follow.add_contents(&src, Path::new("dest"));
+        let targets: Vec<PathBuf> = follow.files.iter().map(|t| t.target.clone()).collect();
+        assert!(targets.contains(&PathBuf::from("dest/link.txt")));
+        assert_eq!(follow.loops, vec![src.join("sub/parent")]);
+        assert_eq!(follow.dangling, vec![src.join("dangling")]);
+    }
+}
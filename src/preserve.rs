@@ -0,0 +1,315 @@
+// File: src\preserve.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Preservation of file metadata on copies
+// License: MIT
+
+//! Metadata preservation
+//!
+//! [`Preserve`] selects which attributes of a source item are carried over
+//! to its copy: permission bits, access/modification times, ownership and
+//! extended attributes. POSIX ACLs are stored as `system.posix_acl_*`
+//! extended attributes and travel with them.
+
+use crate::error::XcomError;
+#[cfg(unix)]
+use crate::logs;
+use filetime::FileTime;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Set of attributes kept when copying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preserve {
+    /// Permission bits (the read-only flag on Windows)
+    pub mode: bool,
+    /// Access and modification times
+    pub timestamps: bool,
+    /// Owner and group, when allowed to change them (usually as root)
+    pub ownership: bool,
+    /// Extended attributes, including POSIX ACLs (Unix only)
+    pub xattr: bool,
+}
+
+impl Preserve {
+    /// Nothing but the data
+    pub const NONE: Preserve = Preserve {
+        mode: false,
+        timestamps: false,
+        ownership: false,
+        xattr: false,
+    };
+
+    /// Every supported attribute
+    pub const ALL: Preserve = Preserve {
+        mode: true,
+        timestamps: true,
+        ownership: true,
+        xattr: true,
+    };
+}
+
+impl Default for Preserve {
+    /// Permissions and extended attributes
+    fn default() -> Self {
+        Preserve {
+            mode: true,
+            xattr: true,
+            ..Preserve::NONE
+        }
+    }
+}
+
+impl fmt::Display for Preserve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = [
+            (self.mode, "mode"),
+            (self.timestamps, "timestamps"),
+            (self.ownership, "ownership"),
+            (self.xattr, "xattr"),
+        ]
+        .iter()
+        .filter(|(kept, _)| *kept)
+        .map(|(_, name)| *name)
+        .collect();
+
+        if names.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&names.join(","))
+        }
+    }
+}
+
+impl FromStr for Preserve {
+    type Err = String;
+
+    /// Parses a comma separated list such as "mode,timestamps", "all" or "none"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut preserve = Preserve::NONE;
+
+        for name in s.split(',').map(|name| name.trim().to_lowercase()) {
+            match name.as_str() {
+                "mode" => preserve.mode = true,
+                "timestamps" => preserve.timestamps = true,
+                "ownership" => preserve.ownership = true,
+                "xattr" => preserve.xattr = true,
+                "all" => preserve = Preserve::ALL,
+                "none" | "" => {}
+                _ => {
+                    return Err(format!(
+                        "Unknown attribute '{}' (expected mode, timestamps, ownership, xattr, \
+                     all or none)",
+                        name
+                    ))
+                }
+            }
+        }
+
+        Ok(preserve)
+    }
+}
+
+/// Gives `target` the attributes of `source` selected by `preserve`
+///
+/// `metadata` belongs to `source` and is read before its data was copied,
+/// so that the access time is not the one of the copy itself.
+///
+/// # Returns
+///
+/// The names of the extended attributes the target could not take, e.g.
+/// because its filesystem does not support them. Those are logged but do
+/// not fail the copy.
+pub(crate) fn apply(
+    source: &Path,
+    metadata: &Metadata,
+    target: &Path,
+    preserve: Preserve,
+) -> Result<Vec<String>, XcomError> {
+    let is_symlink = metadata.file_type().is_symlink();
+    let mut lost = Vec::new();
+
+    // Ownership first: changing the owner clears the setuid and setgid bits
+    // and file capabilities
+    if preserve.ownership {
+        set_owner(metadata, target, is_symlink)?;
+    }
+
+    // Before the mode, which may take away the write access needed here
+    if preserve.xattr {
+        copy_xattrs(source, target, is_symlink, &mut lost)?;
+    }
+
+    if preserve.mode && !is_symlink {
+        fs::set_permissions(target, metadata.permissions())
+            .map_err(|e| XcomError::io("set permissions on", target, e))?;
+    }
+
+    if preserve.timestamps {
+        let accessed = FileTime::from_last_access_time(metadata);
+        let modified = FileTime::from_last_modification_time(metadata);
+        let result = if is_symlink {
+            filetime::set_symlink_file_times(target, accessed, modified)
+        } else {
+            filetime::set_file_times(target, accessed, modified)
+        };
+        result.map_err(|e| XcomError::io("set timestamps on", target, e))?;
+    }
+
+    Ok(lost)
+}
+
+#[cfg(unix)]
+fn set_owner(source: &Metadata, target: &Path, is_symlink: bool) -> Result<(), XcomError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (uid, gid) = (Some(source.uid()), Some(source.gid()));
+    let result = if is_symlink {
+        std::os::unix::fs::lchown(target, uid, gid)
+    } else {
+        std::os::unix::fs::chown(target, uid, gid)
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        // Only root may give files away; like cp, keep the caller as owner
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Ok(()),
+        Err(e) => Err(XcomError::io("set owner of", target, e)),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_owner(_source: &Metadata, _target: &Path, _is_symlink: bool) -> Result<(), XcomError> {
+    Ok(())
+}
+
+/// Copies every extended attribute of `source` to `target`
+///
+/// Attributes the target refuses are added to `lost` instead of failing.
+#[cfg(unix)]
+fn copy_xattrs(
+    source: &Path,
+    target: &Path,
+    is_symlink: bool,
+    lost: &mut Vec<String>,
+) -> Result<(), XcomError> {
+    let names = if is_symlink {
+        xattr::list(source)
+    } else {
+        xattr::list_deref(source)
+    };
+    let names = match names {
+        Ok(names) => names,
+        // Nothing to copy from a filesystem without extended attributes
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return Ok(()),
+        Err(e) => return Err(XcomError::io("list attributes of", source, e)),
+    };
+
+    for name in names {
+        let value = if is_symlink {
+            xattr::get(source, &name)
+        } else {
+            xattr::get_deref(source, &name)
+        };
+        let value = match value {
+            Ok(Some(value)) => value,
+            Ok(None) => continue,
+            Err(e) => return Err(XcomError::io("read attributes of", source, e)),
+        };
+
+        let result = if is_symlink {
+            xattr::set(target, &name, &value)
+        } else {
+            xattr::set_deref(target, &name, &value)
+        };
+        if let Err(e) = result {
+            let name = name.to_string_lossy().to_string();
+            logs(&format!(
+                "WARNING: cannot keep attribute {} on {}: {}",
+                name,
+                target.display(),
+                e
+            ));
+            lost.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(
+    _source: &Path,
+    _target: &Path,
+    _is_symlink: bool,
+    _lost: &mut Vec<String>,
+) -> Result<(), XcomError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_preserve_from_str() {
+        assert_eq!(
+            "mode,timestamps".parse::<Preserve>(),
+            Ok(Preserve {
+                mode: true,
+                timestamps: true,
+                ownership: false,
+                xattr: false,
+            })
+        );
+        assert_eq!("all".parse::<Preserve>(), Ok(Preserve::ALL));
+        assert_eq!("none".parse::<Preserve>(), Ok(Preserve::NONE));
+        assert!("xattr-ish".parse::<Preserve>().is_err());
+        assert_eq!(Preserve::default().to_string(), "mode,xattr");
+    }
+
+    #[test]
+    fn test_apply_timestamps() {
+        let tmp = tempdir().unwrap();
+        let source = tmp.path().join("source");
+        let target = tmp.path().join("target");
+        fs::write(&source, "a").unwrap();
+        fs::write(&target, "a").unwrap();
+
+        let mtime = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(&source, mtime).unwrap();
+
+        let metadata = fs::metadata(&source).unwrap();
+        apply(&source, &metadata, &target, Preserve::ALL).unwrap();
+
+        let copied = fs::metadata(&target).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&copied), mtime);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_copies_xattrs() {
+        let tmp = tempdir().unwrap();
+        let source = tmp.path().join("source");
+        let target = tmp.path().join("target");
+        fs::write(&source, "a").unwrap();
+        fs::write(&target, "a").unwrap();
+
+        if xattr::set(&source, "user.label", b"blue").is_err() {
+            // The temporary directory does not support user attributes
+            return;
+        }
+
+        let metadata = fs::metadata(&source).unwrap();
+        let lost = apply(&source, &metadata, &target, Preserve::default()).unwrap();
+
+        assert!(lost.is_empty());
+        assert_eq!(
+            xattr::get(&target, "user.label").unwrap(),
+            Some(b"blue".to_vec())
+        );
+    }
+}
@@ -0,0 +1,297 @@
+// File: src\progress.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Progress reporting for copy and move operations
+// License: MIT
+
+//! Progress reporting
+//!
+//! The engine feeds a [`ProgressTracker`] as it works, which forwards
+//! snapshots to an optional [`ProgressObserver`]. [`TerminalProgress`] is
+//! the observer used by `copyx` and `xmove` to draw a progress bar.
+
+use crate::report::format_bytes;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Snapshot of an operation in progress
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// File currently being transferred, if any
+    pub current_file: Option<PathBuf>,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Average transfer rate so far, in bytes per second
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes_done as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time left at the current throughput
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.throughput();
+        if rate <= 0.0 {
+            return None;
+        }
+
+        let remaining = self.bytes_total.saturating_sub(self.bytes_done);
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    /// Completed fraction of the bytes (or files, for empty files), 0.0 to 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.bytes_total > 0 {
+            (self.bytes_done as f64 / self.bytes_total as f64).min(1.0)
+        } else if self.files_total > 0 {
+            self.files_done as f64 / self.files_total as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Receives progress updates while an operation runs
+pub trait ProgressObserver: Send + Sync {
+    /// Called whenever a file starts, data is written or a file completes
+    fn update(&self, progress: &Progress);
+
+    /// Called once when the operation is over
+    fn finish(&self, _progress: &Progress) {}
+}
+
+/// Accumulates progress and forwards it to the observer
+pub struct ProgressTracker {
+    observer: Option<Arc<dyn ProgressObserver>>,
+    state: Mutex<Progress>,
+    started: Instant,
+}
+
+impl ProgressTracker {
+    pub fn new(
+        observer: Option<Arc<dyn ProgressObserver>>,
+        files_total: u64,
+        bytes_total: u64,
+    ) -> Self {
+        ProgressTracker {
+            observer,
+            state: Mutex::new(Progress {
+                files_total,
+                bytes_total,
+                ..Default::default()
+            }),
+            started: Instant::now(),
+        }
+    }
+
+    /// A tracker nobody listens to
+    pub fn disabled() -> Self {
+        ProgressTracker::new(None, 0, 0)
+    }
+
+    pub fn start_file(&self, path: &Path) {
+        self.with_state(|state| state.current_file = Some(path.to_path_buf()));
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.with_state(|state| state.bytes_done += bytes);
+    }
+
+    pub fn finish_file(&self) {
+        self.with_state(|state| {
+            state.files_done += 1;
+            state.current_file = None;
+        });
+    }
+
+    /// Current snapshot
+    pub fn snapshot(&self) -> Progress {
+        let mut progress = self.state.lock().unwrap().clone();
+        progress.elapsed = self.started.elapsed();
+        progress
+    }
+
+    /// Tells the observer that the operation is over
+    pub fn finish(&self) {
+        if let Some(observer) = &self.observer {
+            observer.finish(&self.snapshot());
+        }
+    }
+
+    fn with_state(&self, change: impl FnOnce(&mut Progress)) {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+
+        if let Some(observer) = &self.observer {
+            let mut progress = state.clone();
+            drop(state);
+            progress.elapsed = self.started.elapsed();
+            observer.update(&progress);
+        }
+    }
+}
+
+/// Progress bar drawn on stderr
+pub struct TerminalProgress {
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl TerminalProgress {
+    const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+    const BAR_WIDTH: usize = 30;
+
+    pub fn new() -> Self {
+        TerminalProgress {
+            last_draw: Mutex::new(None),
+        }
+    }
+
+    fn draw(&self, progress: &Progress) {
+        let filled = (progress.fraction() * Self::BAR_WIDTH as f64) as usize;
+        let eta = progress
+            .eta()
+            .map(format_duration)
+            .unwrap_or_else(|| "--:--".to_string());
+        let current = progress
+            .current_file
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r\x1b[2K[{}{}] {:>3}% {}/{} files {}/{} {}/s ETA {} {}",
+            "#".repeat(filled),
+            "-".repeat(Self::BAR_WIDTH - filled),
+            (progress.fraction() * 100.0) as u32,
+            progress.files_done,
+            progress.files_total,
+            format_bytes(progress.bytes_done),
+            format_bytes(progress.bytes_total),
+            format_bytes(progress.throughput() as u64),
+            eta,
+            current
+        );
+        let _ = stderr.flush();
+    }
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressObserver for TerminalProgress {
+    fn update(&self, progress: &Progress) {
+        let mut last_draw = self.last_draw.lock().unwrap();
+        let due = last_draw.is_none_or(|at| at.elapsed() >= Self::REDRAW_INTERVAL);
+
+        if due {
+            *last_draw = Some(Instant::now());
+            self.draw(progress);
+        }
+    }
+
+    fn finish(&self, progress: &Progress) {
+        self.draw(progress);
+        eprintln!();
+    }
+}
+
+/// Formats a duration as "mm:ss", or "hh:mm:ss" past an hour
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            (seconds / 60) % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Observer for tests: cancels the operation once more than the given
+/// number of bytes are done
+#[cfg(test)]
+pub(crate) struct CancelAfter(pub crate::CancellationToken, pub u64);
+
+#[cfg(test)]
+impl ProgressObserver for CancelAfter {
+    fn update(&self, progress: &Progress) {
+        if progress.bytes_done > self.1 {
+            self.0.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        updates: Mutex<Vec<Progress>>,
+    }
+
+    impl ProgressObserver for Recorder {
+        fn update(&self, progress: &Progress) {
+            self.updates.lock().unwrap().push(progress.clone());
+        }
+    }
+
+    #[test]
+    fn test_tracker_forwards_updates() {
+        let recorder = Arc::new(Recorder::default());
+        let tracker = ProgressTracker::new(Some(recorder.clone()), 2, 100);
+
+        tracker.start_file(Path::new("a.bin"));
+        tracker.add_bytes(40);
+        tracker.finish_file();
+
+        let updates = recorder.updates.lock().unwrap();
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].current_file, Some(PathBuf::from("a.bin")));
+        assert_eq!(updates[1].bytes_done, 40);
+        assert_eq!(updates[2].files_done, 1);
+        assert_eq!(updates[2].current_file, None);
+    }
+
+    #[test]
+    fn test_eta_and_fraction() {
+        let progress = Progress {
+            files_done: 1,
+            files_total: 4,
+            bytes_done: 50,
+            bytes_total: 200,
+            current_file: None,
+            elapsed: Duration::from_secs(5),
+        };
+
+        assert_eq!(progress.throughput(), 10.0);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(15)));
+        assert_eq!(progress.fraction(), 0.25);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(75)), "01:15");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+    }
+}
@@ -0,0 +1,125 @@
+// File: src\reflink.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Copy-on-write clones and in-kernel copies
+// License: MIT
+
+//! Fast copy paths
+//!
+//! On copy-on-write filesystems such as btrfs and XFS a file can be cloned
+//! without copying its data (a reflink). Elsewhere `copy_file_range` still
+//! lets the kernel copy the data without a round trip through userspace.
+//! [`ReflinkMode`] selects which of them the native engine may use.
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::str::FromStr;
+
+/// Whether copies may share data with their source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReflinkMode {
+    /// Clone when the filesystem can, otherwise copy in the kernel or in
+    /// userspace
+    #[default]
+    Auto,
+    /// Clone, and fail the files that cannot be cloned
+    Always,
+    /// Always copy the data, in userspace
+    Never,
+}
+
+impl ReflinkMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReflinkMode::Auto => "auto",
+            ReflinkMode::Always => "always",
+            ReflinkMode::Never => "never",
+        }
+    }
+}
+
+impl fmt::Display for ReflinkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReflinkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ReflinkMode::Auto),
+            "always" => Ok(ReflinkMode::Always),
+            "never" => Ok(ReflinkMode::Never),
+            _ => Err(format!(
+                "Unknown reflink mode '{}' (expected auto, always or never)",
+                s
+            )),
+        }
+    }
+}
+
+/// Makes `target` a copy-on-write clone of `source`
+#[cfg(target_os = "linux")]
+pub(crate) fn clone_file(source: &File, target: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: FICLONE only reads the source descriptor passed as argument
+    let result = unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn clone_file(_source: &File, _target: &File) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// Copies up to `len` bytes from the current offset of `source` to the
+/// current offset of `target` inside the kernel, advancing both
+///
+/// Returns the number of bytes copied, 0 at the end of the source.
+#[cfg(target_os = "linux")]
+pub(crate) fn copy_range(source: &File, target: &File, len: usize) -> io::Result<usize> {
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    // SAFETY: null offsets make the kernel use and update the file offsets
+    let copied = unsafe {
+        libc::copy_file_range(
+            source.as_raw_fd(),
+            ptr::null_mut(),
+            target.as_raw_fd(),
+            ptr::null_mut(),
+            len,
+            0,
+        )
+    };
+    if copied < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(copied as usize)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn copy_range(_source: &File, _target: &File, _len: usize) -> io::Result<usize> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!("always".parse::<ReflinkMode>(), Ok(ReflinkMode::Always));
+        assert_eq!("AUTO".parse::<ReflinkMode>(), Ok(ReflinkMode::Auto));
+        assert!("maybe".parse::<ReflinkMode>().is_err());
+    }
+}
@@ -0,0 +1,436 @@
+// File: src\report.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Per-item results of a copy or move operation
+// License: MIT
+
+//! Operation reports
+//!
+//! [`process_sources`](crate::process_sources) and
+//! [`perform_operation`](crate::perform_operation) return an
+//! [`OperationReport`] describing what happened to every item.
+
+use crate::error::{FileFailure, XcomError};
+use crate::FileOperation;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// What happened to a single item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Copied,
+    Moved,
+    /// Copied or moved under a new name to avoid a conflict
+    Renamed,
+    /// Left alone, e.g. by the conflict policy
+    Skipped,
+    Failed(XcomError),
+}
+
+impl Outcome {
+    /// Whether the item reached its destination
+    pub fn is_transferred(&self) -> bool {
+        matches!(self, Outcome::Copied | Outcome::Moved | Outcome::Renamed)
+    }
+}
+
+/// How the data of a file reached its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyStrategy {
+    /// Renamed in place, no data copied
+    Rename,
+    /// Copy-on-write clone sharing the data of the source
+    Reflink,
+    /// Copied inside the kernel with `copy_file_range`
+    CopyFileRange,
+    /// Only the data segments were copied, holes were recreated
+    Sparse,
+    /// Read and written through a userspace buffer
+    Buffered,
+}
+
+impl CopyStrategy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CopyStrategy::Rename => "rename",
+            CopyStrategy::Reflink => "reflink",
+            CopyStrategy::CopyFileRange => "copy_file_range",
+            CopyStrategy::Sparse => "sparse",
+            CopyStrategy::Buffered => "buffered",
+        }
+    }
+}
+
+impl fmt::Display for CopyStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Result for a single source item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemReport {
+    pub source: PathBuf,
+    /// Final destination path (the renamed path for renamed items)
+    pub destination: PathBuf,
+    pub outcome: Outcome,
+    /// An existing destination file was (or would be) replaced
+    pub replaced: bool,
+    /// Bytes written to the destination for this item
+    pub bytes: u64,
+    /// Extended attributes (including ACLs) the destination could not keep
+    pub lost_attributes: Vec<String>,
+    /// The destination is a hard link to this earlier copy of the same file
+    pub linked_to: Option<PathBuf>,
+    /// How the native engine transferred the data, when it did
+    pub strategy: Option<CopyStrategy>,
+}
+
+impl ItemReport {
+    /// Short description of the action taken for this item
+    pub fn action(&self) -> &'static str {
+        match &self.outcome {
+            _ if self.linked_to.is_some() && self.outcome.is_transferred() => "link",
+            Outcome::Copied | Outcome::Moved if self.replaced => "overwrite",
+            Outcome::Copied | Outcome::Moved => "create",
+            Outcome::Renamed => "rename",
+            Outcome::Skipped => "skip",
+            Outcome::Failed(_) => "fail",
+        }
+    }
+}
+
+/// Attributes a destination directory did not keep
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirReport {
+    pub destination: PathBuf,
+    /// Extended attributes (including ACLs) the directory could not keep
+    pub lost_attributes: Vec<String>,
+    /// Why the attributes could not be set at all
+    pub error: Option<XcomError>,
+}
+
+/// Result of a whole copy or move operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationReport {
+    pub operation: FileOperation,
+    pub items: Vec<ItemReport>,
+    pub elapsed: Duration,
+    /// The operation was aborted before every item was processed
+    pub aborted: bool,
+    /// Nothing was touched: the items describe what would have happened
+    pub dry_run: bool,
+    /// Symbolic links found whose target does not exist
+    pub dangling_links: Vec<PathBuf>,
+    /// Links not followed because they point to one of their parents
+    pub link_loops: Vec<PathBuf>,
+    /// Destination directories that did not keep all of their attributes
+    pub dirs: Vec<DirReport>,
+}
+
+impl OperationReport {
+    pub fn new(operation: FileOperation) -> Self {
+        OperationReport {
+            operation,
+            items: Vec::new(),
+            elapsed: Duration::ZERO,
+            aborted: false,
+            dry_run: false,
+            dangling_links: Vec::new(),
+            link_loops: Vec::new(),
+            dirs: Vec::new(),
+        }
+    }
+
+    /// Total bytes written to the destination
+    pub fn bytes_transferred(&self) -> u64 {
+        self.items.iter().map(|item| item.bytes).sum()
+    }
+
+    pub fn transferred(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.outcome.is_transferred())
+            .count()
+    }
+
+    pub fn renamed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Renamed))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Skipped))
+    }
+
+    /// Every item that failed, with its error
+    pub fn failures(&self) -> Vec<FileFailure> {
+        self.items
+            .iter()
+            .filter_map(|item| match &item.outcome {
+                Outcome::Failed(error) => Some(FileFailure {
+                    source: item.source.clone(),
+                    target: item.destination.clone(),
+                    error: error.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// True when nothing failed and the operation was not aborted
+    pub fn is_success(&self) -> bool {
+        !self.aborted && self.failures().is_empty()
+    }
+
+    /// Converts failed items into an [`XcomError::Failures`], or an
+    /// [`XcomError::Aborted`] when the operation was aborted
+    pub fn error(&self) -> Option<XcomError> {
+        let failures = self.failures();
+        if failures.is_empty() {
+            None
+        } else if self.aborted {
+            Some(XcomError::Aborted(failures))
+        } else {
+            Some(XcomError::Failures(failures))
+        }
+    }
+
+    fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.items
+            .iter()
+            .filter(|item| predicate(&item.outcome))
+            .count()
+    }
+}
+
+impl fmt::Display for OperationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match (self.operation, self.dry_run) {
+            (FileOperation::Copy, false) => "Copied",
+            (FileOperation::Move, false) => "Moved",
+            (FileOperation::Copy, true) => "Would copy",
+            (FileOperation::Move, true) => "Would move",
+        };
+
+        write!(
+            f,
+            "{} {} item(s) ({} renamed), skipped {}, failed {}: {} in {:.2}s",
+            verb,
+            self.transferred(),
+            self.renamed(),
+            self.skipped(),
+            self.failures().len(),
+            format_bytes(self.bytes_transferred()),
+            self.elapsed.as_secs_f64()
+        )?;
+
+        if !self.dangling_links.is_empty() {
+            write!(f, ", {} dangling link(s)", self.dangling_links.len())?;
+        }
+        if self.aborted {
+            write!(f, " (aborted)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Per-file details reported by the engine while an operation runs
+///
+/// Entries are keyed by the target path of the file they concern and
+/// merged into the [`ItemReport`] whose destination is, or holds, that
+/// path once the backend is done.
+#[derive(Debug, Default)]
+pub struct ItemNotes {
+    lost_attributes: Mutex<BTreeMap<PathBuf, Vec<String>>>,
+    strategies: Mutex<BTreeMap<PathBuf, CopyStrategy>>,
+}
+
+impl ItemNotes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records attributes that could not be kept on `target`
+    pub fn lost_attributes(&self, target: &Path, attributes: Vec<String>) {
+        if attributes.is_empty() {
+            return;
+        }
+
+        self.lost_attributes
+            .lock()
+            .unwrap()
+            .entry(target.to_path_buf())
+            .or_default()
+            .extend(attributes);
+    }
+
+    /// Records how the data of `target` was transferred
+    pub fn strategy(&self, target: &Path, strategy: CopyStrategy) {
+        self.strategies
+            .lock()
+            .unwrap()
+            .insert(target.to_path_buf(), strategy);
+    }
+
+    /// Moves the notes recorded for `item`'s destination, and for the
+    /// files below it when it is a directory, into it
+    ///
+    /// Attributes lost by files below the destination are prefixed with
+    /// their relative path. The strategy of a directory is the one all of
+    /// its files share, if any.
+    pub(crate) fn fill(&self, item: &mut ItemReport) {
+        let destination = &item.destination;
+
+        let lost = take_below(&mut self.lost_attributes.lock().unwrap(), destination);
+        for (path, attributes) in lost {
+            match path.strip_prefix(destination) {
+                Ok(relative) if !relative.as_os_str().is_empty() => {
+                    item.lost_attributes.extend(
+                        attributes
+                            .into_iter()
+                            .map(|attribute| format!("{}: {}", relative.display(), attribute)),
+                    );
+                }
+                _ => item.lost_attributes.extend(attributes),
+            }
+        }
+
+        let strategies = take_below(&mut self.strategies.lock().unwrap(), destination);
+        let mut strategies = strategies.into_iter().map(|(_, strategy)| strategy);
+        item.strategy = strategies
+            .next()
+            .filter(|first| strategies.all(|strategy| strategy == *first));
+    }
+}
+
+/// Removes the entries of `path` and of everything below it from `map`
+fn take_below<T>(map: &mut BTreeMap<PathBuf, T>, path: &Path) -> Vec<(PathBuf, T)> {
+    // Paths order by component, so what lies below `path` comes right after it
+    let keys: Vec<PathBuf> = map
+        .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+        .map(|(key, _)| key)
+        .take_while(|key| key.starts_with(path))
+        .cloned()
+        .collect();
+
+    keys.into_iter()
+        .filter_map(|key| map.remove(&key).map(|value| (key, value)))
+        .collect()
+}
+
+/// Formats a byte count with a binary unit, e.g. "1.50 MiB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(outcome: Outcome, bytes: u64) -> ItemReport {
+        ItemReport {
+            source: PathBuf::from("a"),
+            destination: PathBuf::from("dest/a"),
+            outcome,
+            replaced: false,
+            bytes,
+            lost_attributes: Vec::new(),
+            linked_to: None,
+            strategy: None,
+        }
+    }
+
+    #[test]
+    fn test_report_counts() {
+        let mut report = OperationReport::new(FileOperation::Copy);
+        report.items.push(item(Outcome::Copied, 10));
+        report.items.push(item(Outcome::Renamed, 5));
+        report.items.push(item(Outcome::Skipped, 0));
+        report.items.push(item(
+            Outcome::Failed(XcomError::invalid(Path::new("a"), "broken")),
+            0,
+        ));
+
+        assert_eq!(report.transferred(), 2);
+        assert_eq!(report.renamed(), 1);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.bytes_transferred(), 15);
+        assert!(!report.is_success());
+        assert_eq!(report.error().unwrap().failures().len(), 1);
+    }
+
+    #[test]
+    fn test_item_action() {
+        let mut overwritten = item(Outcome::Moved, 1);
+        overwritten.replaced = true;
+
+        assert_eq!(item(Outcome::Copied, 1).action(), "create");
+        assert_eq!(overwritten.action(), "overwrite");
+        assert_eq!(item(Outcome::Skipped, 0).action(), "skip");
+
+        let mut linked = item(Outcome::Copied, 0);
+        linked.linked_to = Some(PathBuf::from("dest/b"));
+        assert_eq!(linked.action(), "link");
+    }
+
+    #[test]
+    fn test_notes_fill_matching_item() {
+        let notes = ItemNotes::new();
+        notes.lost_attributes(Path::new("dest/a"), vec!["user.label".to_string()]);
+        notes.lost_attributes(Path::new("dest/b"), vec!["user.other".to_string()]);
+        notes.strategy(Path::new("dest/a"), CopyStrategy::Reflink);
+
+        let mut report = item(Outcome::Copied, 1);
+        notes.fill(&mut report);
+        assert_eq!(report.lost_attributes, vec!["user.label"]);
+        assert_eq!(report.strategy, Some(CopyStrategy::Reflink));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.00 GiB");
+    }
+
+    #[test]
+    fn test_notes_below_a_directory_reach_its_item() {
+        let notes = ItemNotes::new();
+        notes.lost_attributes(Path::new("dest/a/sub/f.txt"), vec!["user.x".to_string()]);
+        notes.lost_attributes(Path::new("dest/ab"), vec!["user.y".to_string()]);
+        notes.strategy(Path::new("dest/a/sub/f.txt"), CopyStrategy::Buffered);
+        notes.strategy(Path::new("dest/a/g.txt"), CopyStrategy::Buffered);
+
+        let mut dir = item(Outcome::Moved, 0);
+        notes.fill(&mut dir);
+        assert_eq!(
+            dir.lost_attributes,
+            vec![format!("{}: user.x", Path::new("sub/f.txt").display())]
+        );
+        assert_eq!(dir.strategy, Some(CopyStrategy::Buffered));
+
+        let mut other = item(Outcome::Moved, 0);
+        other.destination = PathBuf::from("dest/ab");
+        notes.fill(&mut other);
+        assert_eq!(other.lost_attributes, vec!["user.y"]);
+        assert_eq!(other.strategy, None);
+    }
+}
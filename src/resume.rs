@@ -0,0 +1,181 @@
+// File: src\resume.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Journals of interrupted copies
+// License: MIT
+
+//! Resumable copies
+//!
+//! With [`OperationOptions::resume`](crate::OperationOptions::resume), a
+//! file larger than [`CHECKPOINT_INTERVAL`] gets a small journal next to its
+//! target while it is copied, e.g. `disk.img.xcom-journal`. The journal
+//! records how much of the copy is known to be on disk. When the copy is
+//! interrupted, the partial copy (still under its temporary name) and its
+//! journal are kept; the next run checks the copied prefix against the
+//! source and continues after it.
+
+use crate::cancel::CancellationToken;
+use crate::error::XcomError;
+use crate::logs;
+use crate::native;
+use crate::verify::{self, HashAlgorithm};
+use std::fs::{self, File, Metadata};
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Bytes copied between two updates of a journal; smaller files have none
+pub(crate) const CHECKPOINT_INTERVAL: u64 = 64 << 20;
+
+const JOURNAL_SUFFIX: &str = ".xcom-journal";
+
+/// Path of the journal kept for `target`
+pub(crate) fn journal_path(target: &Path) -> PathBuf {
+    native::sibling_path(target, "", JOURNAL_SUFFIX)
+}
+
+/// Whether an interrupted run left a partial copy of `target`
+pub(crate) fn has_journal(target: &Path) -> bool {
+    journal_path(target).is_file()
+}
+
+/// Removes the journal of `target`, if any
+pub(crate) fn remove_journal(target: &Path) {
+    let _ = fs::remove_file(journal_path(target));
+}
+
+/// Journal of the copy of one file
+pub(crate) struct Journal {
+    path: PathBuf,
+    /// Identifies the source, so that a changed source is copied again
+    header: String,
+}
+
+impl Journal {
+    /// Journal of the copy of `source`, described by `metadata`, to `target`
+    pub(crate) fn new(source: &Path, target: &Path, metadata: &Metadata) -> Self {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_nanos());
+
+        Journal {
+            path: journal_path(target),
+            header: format!(
+                "source={}\nlength={}\nmodified={}\n",
+                source.display(),
+                metadata.len(),
+                modified
+            ),
+        }
+    }
+
+    /// Offset recorded by an earlier run of the same copy
+    fn recorded_offset(&self) -> Option<u64> {
+        let contents = fs::read_to_string(&self.path).ok()?;
+        contents
+            .strip_prefix(&self.header)?
+            .trim_end()
+            .strip_prefix("offset=")?
+            .parse()
+            .ok()
+    }
+
+    /// Records that the first `offset` bytes of the target are on disk
+    pub(crate) fn record(&self, offset: u64) -> Result<(), XcomError> {
+        fs::write(&self.path, format!("{}offset={}\n", self.header, offset))
+            .map_err(|e| XcomError::io("write", &self.path, e))
+    }
+
+    /// Flushes `writer` to disk and records how far it got
+    pub(crate) fn checkpoint(&self, writer: &File, target: &Path) -> Result<(), XcomError> {
+        let mut file = writer;
+        let offset = file
+            .stream_position()
+            .map_err(|e| XcomError::io("write", target, e))?;
+
+        // Blocks of zeros skipped at the end are not part of the file yet
+        let len = writer
+            .metadata()
+            .map_err(|e| XcomError::io("write", target, e))?
+            .len();
+        if len < offset {
+            writer
+                .set_len(offset)
+                .map_err(|e| XcomError::io("write", target, e))?;
+        }
+
+        writer
+            .sync_data()
+            .map_err(|e| XcomError::io("flush", target, e))?;
+        self.record(offset)
+    }
+}
+
+/// Returns the offset the interrupted copy in `partial` can continue from
+///
+/// The prefix copied so far is compared with the source first; 0 means
+/// that there is nothing to resume, or that it has to be copied again.
+pub(crate) fn resume_offset(
+    journal: &Journal,
+    source: &Path,
+    partial: &Path,
+    cancel: &CancellationToken,
+) -> Result<u64, XcomError> {
+    let offset = match journal.recorded_offset() {
+        Some(offset) if offset > 0 => offset,
+        _ => return Ok(0),
+    };
+    let copied = fs::metadata(partial).map_or(0, |metadata| metadata.len());
+    if copied < offset {
+        return Ok(0);
+    }
+
+    let algorithm = HashAlgorithm::Blake3;
+    let expected = verify::hash_file(source, algorithm, Some(offset), cancel)?;
+    let actual = verify::hash_file(partial, algorithm, Some(offset), cancel)?;
+    if expected != actual {
+        logs(&format!(
+            "WARNING: \"{}\" differs from its source, copying it again",
+            partial.display()
+        ));
+        return Ok(0);
+    }
+
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resume_offset_checks_prefix() {
+        let tmp = tempdir().unwrap();
+        let source = tmp.path().join("source");
+        let target = tmp.path().join("target");
+        fs::write(&source, "0123456789").unwrap();
+        fs::write(&target, "01234").unwrap();
+
+        let metadata = fs::metadata(&source).unwrap();
+        let journal = Journal::new(&source, &target, &metadata);
+        let cancel = CancellationToken::new();
+        assert_eq!(resume_offset(&journal, &source, &target, &cancel), Ok(0));
+
+        journal.record(4).unwrap();
+        assert!(has_journal(&target));
+        assert_eq!(resume_offset(&journal, &source, &target, &cancel), Ok(4));
+
+        fs::write(&target, "x1234").unwrap();
+        assert_eq!(resume_offset(&journal, &source, &target, &cancel), Ok(0));
+
+        // Another source never matches the journal
+        let other = Journal::new(&target, &target, &metadata);
+        assert_eq!(resume_offset(&other, &source, &target, &cancel), Ok(0));
+
+        remove_journal(&target);
+        assert!(!has_journal(&target));
+    }
+}
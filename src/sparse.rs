@@ -0,0 +1,126 @@
+// File: src\sparse.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Detection of holes in sparse files
+// License: MIT
+
+//! Sparse files
+//!
+//! Disk images and database files are often mostly holes. [`SparseMode`]
+//! decides whether the native engine recreates them at the destination
+//! instead of writing out every zero.
+
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::str::FromStr;
+
+/// How holes in the copied files are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SparseMode {
+    /// Keep the holes of sparse sources
+    #[default]
+    Auto,
+    /// Keep the holes, and turn runs of zeros into holes as well
+    Always,
+    /// Write every byte, holes included
+    Never,
+}
+
+impl SparseMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SparseMode::Auto => "auto",
+            SparseMode::Always => "always",
+            SparseMode::Never => "never",
+        }
+    }
+}
+
+impl fmt::Display for SparseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SparseMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(SparseMode::Auto),
+            "always" => Ok(SparseMode::Always),
+            "never" => Ok(SparseMode::Never),
+            _ => Err(format!(
+                "Unknown sparse mode '{}' (expected auto, always or never)",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns true if the file occupies less disk space than its length
+#[cfg(unix)]
+pub(crate) fn has_holes(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks().saturating_mul(512) < metadata.len()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn has_holes(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Lists the data segments of `file` as (offset, length) pairs
+///
+/// Everything between them is a hole. Returns `None` when the filesystem
+/// cannot tell data from holes.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub(crate) fn data_segments(file: &File, len: u64) -> Option<Vec<(u64, u64)>> {
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let mut segments = Vec::new();
+    let mut offset = 0;
+
+    while offset < len {
+        // SAFETY: lseek only moves the offset of a descriptor we own
+        let data = unsafe { libc::lseek(fd, offset as libc::off_t, libc::SEEK_DATA) };
+        if data < 0 {
+            // ENXIO: nothing but a hole up to the end of the file
+            return match io::Error::last_os_error().raw_os_error() {
+                Some(libc::ENXIO) => Some(segments),
+                _ => None,
+            };
+        }
+
+        // SAFETY: as above
+        let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return None;
+        }
+
+        let end = (hole as u64).min(len);
+        segments.push((data as u64, end.saturating_sub(data as u64)));
+        offset = end;
+    }
+
+    Some(segments)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub(crate) fn data_segments(_file: &File, _len: u64) -> Option<Vec<(u64, u64)>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!("always".parse::<SparseMode>(), Ok(SparseMode::Always));
+        assert_eq!("Never".parse::<SparseMode>(), Ok(SparseMode::Never));
+        assert!("sometimes".parse::<SparseMode>().is_err());
+    }
+}
@@ -0,0 +1,74 @@
+// File: src\symlink.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-16
+// Description: Handling of symbolic links found in the sources
+// License: MIT
+
+//! Symbolic links
+//!
+//! [`SymlinkMode`] decides whether links are copied as links, replaced by
+//! what they point to, or left out. Moves always move links themselves,
+//! unless they are skipped.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// What to do with symbolic links among the sources
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkMode {
+    /// Recreate the link at the destination, pointing to the same path
+    #[default]
+    Copy,
+    /// Copy what the link points to; links to a parent are not followed
+    Follow,
+    /// Leave links out
+    Skip,
+}
+
+impl SymlinkMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SymlinkMode::Copy => "copy",
+            SymlinkMode::Follow => "follow",
+            SymlinkMode::Skip => "skip",
+        }
+    }
+}
+
+impl fmt::Display for SymlinkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SymlinkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "copy" | "preserve" => Ok(SymlinkMode::Copy),
+            "follow" | "dereference" => Ok(SymlinkMode::Follow),
+            "skip" => Ok(SymlinkMode::Skip),
+            _ => Err(format!(
+                "Unknown symlink mode '{}' (expected copy, follow or skip)",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!("follow".parse::<SymlinkMode>(), Ok(SymlinkMode::Follow));
+        assert_eq!(
+            "dereference".parse::<SymlinkMode>(),
+            Ok(SymlinkMode::Follow)
+        );
+        assert_eq!("SKIP".parse::<SymlinkMode>(), Ok(SymlinkMode::Skip));
+        assert!("hardlink".parse::<SymlinkMode>().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+// File: src\verify.rs
+// Author: Hadi Cahyadi <cumulus13@gmail.com>
+// Date: 2026-10-17
+// Description: Checksum verification of copied files
+// License: MIT
+
+//! Copy verification
+//!
+//! With a [`HashAlgorithm`] selected in
+//! [`OperationOptions::verify`](crate::OperationOptions::verify), every
+//! copied file is read back and hashed along with its source. A mismatch
+//! fails the file, and a move across devices keeps its source.
+
+use crate::cancel::CancellationToken;
+use crate::error::XcomError;
+use sha2::Digest;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Size of the buffer used to read files being hashed
+const HASH_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Checksum used to compare a copy with its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+    /// 128-bit XXH3, fast but not cryptographic
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "xxh3" => Ok(HashAlgorithm::Xxh3),
+            _ => Err(format!(
+                "Unknown hash algorithm '{}' (expected blake3, sha256 or xxh3)",
+                s
+            )),
+        }
+    }
+}
+
+/// Incremental state of one of the algorithms
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Xxh3(hasher) => hasher.digest128().to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// Hashes the contents of `path`, or its first `limit` bytes, checking for
+/// cancellation between chunks
+pub(crate) fn hash_file(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    limit: Option<u64>,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>, XcomError> {
+    let file = File::open(path).map_err(|e| XcomError::io("open", path, e))?;
+    let mut file = file.take(limit.unwrap_or(u64::MAX));
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+
+    loop {
+        if cancel.is_cancelled() {
+            return Err(XcomError::Cancelled);
+        }
+
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| XcomError::io("read", path, e))?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// Compares `target` with `source`, failing if their contents differ
+///
+/// `written` is the file the copy was written through. It is flushed to
+/// the device and dropped from the page cache first, so that the target is
+/// read back from the device rather than from memory.
+pub(crate) fn verify_copy(
+    source: &Path,
+    target: &Path,
+    written: &File,
+    algorithm: HashAlgorithm,
+    cancel: &CancellationToken,
+) -> Result<(), XcomError> {
+    written
+        .sync_data()
+        .map_err(|e| XcomError::io("flush", target, e))?;
+    drop_cache(written);
+
+    let expected = hash_file(source, algorithm, None, cancel)?;
+    let actual = hash_file(target, algorithm, None, cancel)?;
+    if expected == actual {
+        return Ok(());
+    }
+
+    Err(XcomError::invalid(
+        target,
+        format!(
+            "{} checksum mismatch (source {}, copy {})",
+            algorithm,
+            to_hex(&expected),
+            to_hex(&actual)
+        ),
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn drop_cache(file: &File) {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: only advises the kernel about a descriptor we own
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn drop_cache(_file: &File) {}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_algorithm_from_str() {
+        assert_eq!("SHA256".parse::<HashAlgorithm>(), Ok(HashAlgorithm::Sha256));
+        assert_eq!("xxh3".parse::<HashAlgorithm>(), Ok(HashAlgorithm::Xxh3));
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_verify_detects_mismatch() {
+        let tmp = tempdir().unwrap();
+        let source = tmp.path().join("source");
+        let target = tmp.path().join("target");
+        fs::write(&source, "same data").unwrap();
+        fs::write(&target, "same data").unwrap();
+        let cancel = CancellationToken::new();
+
+        for algorithm in [
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Xxh3,
+        ] {
+            let written = fs::OpenOptions::new().write(true).open(&target).unwrap();
+            assert!(verify_copy(&source, &target, &written, algorithm, &cancel).is_ok());
+        }
+
+        fs::write(&target, "other data").unwrap();
+        let written = fs::OpenOptions::new().write(true).open(&target).unwrap();
+        let error = verify_copy(&source, &target, &written, HashAlgorithm::Blake3, &cancel);
+        assert!(error.unwrap_err().to_string().contains("checksum mismatch"));
+    }
+}